@@ -0,0 +1,120 @@
+use crate::{graph::*, hash};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+impl<T, E> Graph<T, E> {
+    // Keys reachable from root in postorder
+    fn postorder_keys(&self, root: u64) -> Vec<u64> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert(root);
+        let mut stack = vec![(root, self.nodes[&root].edges.keys())];
+        while let Some((key, targets)) = stack.last_mut() {
+            match targets.next() {
+                Some(target) => {
+                    if visited.insert(*target) {
+                        let next = self.nodes[target].edges.keys();
+                        stack.push((*target, next));
+                    }
+                }
+                None => {
+                    order.push(*key);
+                    stack.pop();
+                }
+            }
+        }
+        order
+    }
+
+    // Immediate dominator of every node reachable from root by Cooper, Harvey and Kennedy
+    pub(crate) fn immediate_dominators(&self, root: u64) -> HashMap<u64, u64> {
+        let postorder = self.postorder_keys(root);
+        let numbers: HashMap<u64, usize> =
+            postorder.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+        let predecessors = self.predecessor_keys();
+
+        let mut idoms = HashMap::new();
+        idoms.insert(root, root);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for key in postorder.iter().rev().skip(1) {
+                let mut processed = predecessors[key].iter().filter(|p| idoms.contains_key(*p));
+                let first = match processed.next() {
+                    Some(first) => *first,
+                    None => continue,
+                };
+
+                let idom = processed.fold(first, |mut a, b| {
+                    let mut b = *b;
+                    while a != b {
+                        while numbers[&a] < numbers[&b] {
+                            a = idoms[&a];
+                        }
+                        while numbers[&b] < numbers[&a] {
+                            b = idoms[&b];
+                        }
+                    }
+                    a
+                });
+                if idoms.get(key) != Some(&idom) {
+                    idoms.insert(*key, idom);
+                    changed = true;
+                }
+            }
+        }
+        idoms
+    }
+}
+
+impl<T: Hash + Eq + Clone, E> Graph<T, E> {
+    // Every node reachable from root pointing to its immediate dominator
+    pub fn dominator_tree(&self, root: &T) -> Option<Graph<T>> {
+        self.get(root)?;
+        let idoms = self.immediate_dominators(hash(root));
+
+        let mut tree = Graph::new();
+        for key in idoms.keys() {
+            tree.add(self.nodes[key].label.clone());
+        }
+        for (key, idom) in &idoms {
+            if key != idom {
+                tree.connect(&self.nodes[key].label, &self.nodes[idom].label);
+            }
+        }
+        Some(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dominator_tree() {
+        let mut g = Graph::init('a'..='g');
+
+        // a -> b -> c -> e -> f
+        //      b -> d -> e
+        //           d <- f
+        //                     g
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'e'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.connect(&'e', &'f'));
+        assert!(g.connect(&'f', &'d'));
+
+        let tree = g.dominator_tree(&'a').unwrap();
+        assert_eq!(tree.edges().count(), 5);
+        assert!(tree.is_connected(&'b', &'a'));
+        assert!(tree.is_connected(&'c', &'b'));
+        assert!(tree.is_connected(&'d', &'b'));
+        assert!(tree.is_connected(&'e', &'b'));
+        assert!(tree.is_connected(&'f', &'e'));
+        assert!(tree.connections(&'g').is_none());
+
+        assert!(g.dominator_tree(&'h').is_none());
+    }
+}
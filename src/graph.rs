@@ -1,16 +1,173 @@
-use crate::hash;
+use crate::{hash, unmap, Map};
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
 use std::hash::Hash;
+use std::mem;
+use std::sync::Mutex;
 
-#[derive(Debug)]
-pub struct Graph<T> {
-    pub(crate) nodes: HashMap<u64, Node<T>>,
+#[derive(Debug, Clone)]
+pub struct Graph<T, E = ()> {
+    pub(crate) nodes: Map<u64, Node<T, E>>,
+    pub(crate) history: Option<History<T>>,
+    pub(crate) endpoints: Option<Endpoints>,
+    pub(crate) query_cache: Option<QueryCache>,
+    pub(crate) degree_limits: DegreeLimits,
 }
 
-impl<T> Default for Graph<T> {
+impl<T, E> Default for Graph<T, E> {
     fn default() -> Self {
         Graph {
-            nodes: HashMap::new(),
+            nodes: Map::new(),
+            history: None,
+            endpoints: None,
+            query_cache: None,
+            degree_limits: DegreeLimits::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DegreeLimits {
+    max_in: Option<usize>,
+    max_out: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct History<T> {
+    clone: fn(&T) -> T,
+    ops: Vec<Op<T>>,
+}
+
+// Each node's label and weighted edges from before a bulk edit, to diff against after it
+pub(crate) type Checkpoint<T> = Map<u64, (T, Map<u64, i64>)>;
+
+// Least recently used descendant sets by node key, emptied by any edit. Behind a lock
+// so queries through a shared reference can fill it.
+#[derive(Debug)]
+pub(crate) struct QueryCache(Mutex<Lru>);
+
+#[derive(Debug, Clone)]
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<u64, (u64, HashSet<u64>)>, // value is last use and descendants
+}
+
+impl Clone for QueryCache {
+    fn clone(&self) -> Self {
+        QueryCache(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+impl QueryCache {
+    pub(crate) fn get_or_insert_with<F>(&self, key: u64, f: F) -> HashSet<u64>
+    where
+        F: FnOnce() -> HashSet<u64>,
+    {
+        {
+            let mut lru = self.0.lock().unwrap();
+            lru.tick += 1;
+            let tick = lru.tick;
+            if let Some((used, descendants)) = lru.entries.get_mut(&key) {
+                *used = tick;
+                return descendants.clone();
+            }
+        }
+
+        let descendants = f();
+        let mut lru = self.0.lock().unwrap();
+        if lru.capacity == 0 {
+            return descendants;
+        }
+        if lru.entries.len() >= lru.capacity {
+            let oldest = lru.entries.iter().min_by_key(|(_, (used, _))| *used);
+            let oldest = *oldest.unwrap().0;
+            lru.entries.remove(&oldest);
+        }
+        let tick = lru.tick;
+        lru.entries.insert(key, (tick, descendants.clone()));
+        descendants
+    }
+
+    fn clear(&mut self) {
+        self.0.get_mut().unwrap().entries.clear();
+    }
+}
+
+// Roots and leaves kept up to date edit by edit, once tracking is turned on
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoints {
+    in_degrees: HashMap<u64, usize>,
+    roots: HashSet<u64>,
+    leaves: HashSet<u64>,
+}
+
+impl Endpoints {
+    fn new<T, E>(nodes: &Map<u64, Node<T, E>>) -> Self {
+        let mut endpoints = Endpoints {
+            in_degrees: nodes.keys().map(|k| (*k, 0)).collect(),
+            roots: HashSet::new(),
+            leaves: HashSet::new(),
+        };
+        for (key, node) in nodes {
+            for target in node.edges.keys() {
+                if let Some(degree) = endpoints.in_degrees.get_mut(target) {
+                    *degree += 1;
+                }
+            }
+            if node.edges.is_empty() {
+                endpoints.leaves.insert(*key);
+            }
+        }
+        for (key, degree) in &endpoints.in_degrees {
+            if *degree == 0 {
+                endpoints.roots.insert(*key);
+            }
+        }
+        endpoints
+    }
+
+    fn added(&mut self, key: u64) {
+        self.in_degrees.insert(key, 0);
+        self.roots.insert(key);
+        self.leaves.insert(key);
+    }
+
+    // Sources are the nodes which lost their edge to the removed node
+    fn removed(&mut self, key: u64, targets: &[u64], sources: &[(u64, bool)]) {
+        self.in_degrees.remove(&key);
+        self.roots.remove(&key);
+        self.leaves.remove(&key);
+        for target in targets.iter().filter(|t| **t != key) {
+            self.lose_in_edge(*target);
+        }
+        for (source, leaf) in sources {
+            if *leaf {
+                self.leaves.insert(*source);
+            }
+        }
+    }
+
+    fn connected(&mut self, from: u64, to: u64) {
+        *self.in_degrees.entry(to).or_insert(0) += 1;
+        self.roots.remove(&to);
+        self.leaves.remove(&from);
+    }
+
+    fn disconnected(&mut self, from: u64, to: u64, leaf: bool) {
+        self.lose_in_edge(to);
+        if leaf {
+            self.leaves.insert(from);
+        }
+    }
+
+    fn lose_in_edge(&mut self, key: u64) {
+        if let Some(degree) = self.in_degrees.get_mut(&key) {
+            *degree -= 1;
+            if *degree == 0 {
+                self.roots.insert(key);
+            }
         }
     }
 }
@@ -21,6 +178,233 @@ impl<T> Graph<T> {
     }
 }
 
+impl<T, E> Graph<T, E> {
+    pub fn history(&self) -> &[Op<T>] {
+        match &self.history {
+            Some(history) => &history.ops,
+            None => &[],
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.ops.clear();
+        }
+    }
+
+    fn record<F: FnOnce(fn(&T) -> T) -> Op<T>>(&mut self, op: F) {
+        if let Some(history) = &mut self.history {
+            let op = op(history.clone);
+            history.ops.push(op);
+        }
+    }
+
+    // Only taken while recording history, since diffing is the one use for it
+    pub(crate) fn bulk_checkpoint(&self) -> Option<Checkpoint<T>> {
+        let clone = self.history.as_ref()?.clone;
+        let nodes = self.nodes.iter();
+        let checkpoint = nodes.map(|(k, n)| (*k, (clone(&n.label), n.edges.clone())));
+        Some(checkpoint.collect())
+    }
+
+    // Records a bulk edit as the primitive ops which replay it: removes, then adds, then
+    // disconnects between surviving nodes, then connects and weight changes
+    pub(crate) fn record_bulk(&mut self, before: Option<Checkpoint<T>>) {
+        let (history, before) = match (&mut self.history, before) {
+            (Some(history), Some(before)) => (history, before),
+            _ => return,
+        };
+        let (clone, nodes) = (history.clone, &self.nodes);
+        let label = |key: &u64| clone(&nodes[key].label);
+
+        for (key, (old, _)) in &before {
+            if !nodes.contains_key(key) {
+                history.ops.push(Op::Remove(clone(old)));
+            }
+        }
+        for (key, node) in nodes {
+            if !before.contains_key(key) {
+                history.ops.push(Op::Add(clone(&node.label)));
+            }
+        }
+        for (key, (old, edges)) in &before {
+            let node = match nodes.get(key) {
+                Some(node) => node,
+                None => continue,
+            };
+            for target in edges.keys() {
+                if !node.edges.contains_key(target) && nodes.contains_key(target) {
+                    history.ops.push(Op::Disconnect(clone(old), label(target)));
+                }
+            }
+        }
+        for (key, node) in nodes {
+            let edges = before.get(key).map(|(_, edges)| edges);
+            for (target, weight) in &node.edges {
+                let previous = edges.and_then(|edges| edges.get(target));
+                if previous.is_none() {
+                    history.ops.push(Op::Connect(label(key), label(target)));
+                }
+                if previous.copied().unwrap_or(1) != *weight {
+                    history
+                        .ops
+                        .push(Op::SetWeight(label(key), label(target), *weight));
+                }
+            }
+        }
+    }
+
+    // From now on roots and leaves are maintained through every edit, so reading them
+    // needs no walk over the graph. Bulk edits rebuild them from scratch.
+    pub fn with_endpoint_tracking(mut self) -> Self {
+        if self.endpoints.is_none() {
+            self.endpoints = Some(Endpoints::new(&self.nodes));
+        }
+        self
+    }
+
+    // Each current root once, None unless tracking endpoints
+    pub fn cached_roots(&self) -> Option<impl Iterator<Item = &T> + '_> {
+        let endpoints = self.endpoints.as_ref()?;
+        Some(endpoints.roots.iter().map(move |k| &self.nodes[k].label))
+    }
+
+    // Each current leaf once, None unless tracking endpoints
+    pub fn cached_leaves(&self) -> Option<impl Iterator<Item = &T> + '_> {
+        let endpoints = self.endpoints.as_ref()?;
+        Some(endpoints.leaves.iter().map(move |k| &self.nodes[k].label))
+    }
+
+    // Memoizes up to capacity descendant sets for descendants and has_path,
+    // all of which are forgotten on any edit
+    pub fn with_query_cache(mut self, capacity: usize) -> Self {
+        self.query_cache = Some(QueryCache(Mutex::new(Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        })));
+        self
+    }
+
+    // From now on connecting fails rather than give a node more than limit outgoing edges.
+    // Edges already there are kept and bulk edits like contract_edge aren't checked.
+    pub fn max_out_degree(mut self, limit: usize) -> Self {
+        self.degree_limits.max_out = Some(limit);
+        self
+    }
+
+    // As max_out_degree but for incoming edges
+    pub fn max_in_degree(mut self, limit: usize) -> Self {
+        self.degree_limits.max_in = Some(limit);
+        self
+    }
+
+    fn in_degree(&self, key: u64) -> usize {
+        self.nodes
+            .values()
+            .filter(|node| node.edges.contains_key(&key))
+            .count()
+    }
+
+    // Whether adding the edges not already there would exceed a limit, assumes all ends exist
+    fn check_degrees(&self, edges: &[(u64, u64)]) -> Result<(), ConnectError> {
+        let DegreeLimits { max_in, max_out } = self.degree_limits;
+        if max_in.is_none() && max_out.is_none() {
+            return Ok(());
+        }
+
+        let mut seen = HashSet::new();
+        let mut out_degrees = HashMap::new();
+        let mut in_degrees = HashMap::new();
+        for (a, b) in edges {
+            if self.nodes[a].edges.contains_key(b) || !seen.insert((*a, *b)) {
+                continue;
+            }
+            let out_degree = out_degrees
+                .entry(*a)
+                .or_insert_with(|| self.nodes[a].edges.len());
+            *out_degree += 1;
+            let in_degree = in_degrees.entry(*b).or_insert_with(|| self.in_degree(*b));
+            *in_degree += 1;
+            if max_out.is_some_and(|max| *out_degree > max)
+                || max_in.is_some_and(|max| *in_degree > max)
+            {
+                return Err(ConnectError::DegreeExceeded);
+            }
+        }
+        Ok(())
+    }
+
+    // Keeps caches current after a single edit to the edges or nodes
+    fn edited<F: FnOnce(&mut Endpoints)>(&mut self, f: F) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+        if let Some(endpoints) = &mut self.endpoints {
+            f(endpoints);
+        }
+    }
+
+    // Rebuilds caches after an edit too broad to follow incrementally
+    pub(crate) fn edited_in_bulk(&mut self) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+        if self.endpoints.is_some() {
+            self.endpoints = Some(Endpoints::new(&self.nodes));
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+        for node in self.nodes.values_mut() {
+            node.edges.shrink_to_fit();
+            node.data.shrink_to_fit();
+        }
+    }
+
+    pub fn label_for_key(&self, key: u64) -> Option<&T> {
+        self.nodes.get(&key).map(|node| &node.label)
+    }
+
+    pub(crate) fn in_degrees(&self) -> Map<u64, usize> {
+        let mut degrees: Map<u64, usize> = self.nodes.keys().map(|k| (*k, 0)).collect();
+        for node in self.nodes.values() {
+            for target in node.edges.keys() {
+                if let Some(degree) = degrees.get_mut(target) {
+                    *degree += 1;
+                }
+            }
+        }
+        degrees
+    }
+}
+
+impl<T: Clone, E> Graph<T, E> {
+    // From now on every successful edit is recorded, so replaying the history onto the
+    // graph as it was reproduces its nodes, edges and weights. Bulk edits are recorded as
+    // the primitive ops they amount to. Edge data isn't recorded.
+    pub fn with_history(mut self) -> Self {
+        if self.history.is_none() {
+            self.history = Some(History {
+                clone: T::clone,
+                ops: Vec::new(),
+            });
+        }
+        self
+    }
+}
+
+impl<T: Clone, E: Clone> Graph<T, E> {
+    pub fn snapshot(&self) -> Snapshot<T, E> {
+        Snapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot<T, E>) {
+        *self = snapshot.0;
+    }
+}
+
 impl<T: Hash + Eq + Default> Graph<T> {
     pub fn init<I: IntoIterator<Item = T>>(labels: I) -> Self {
         let mut graph = Self::new();
@@ -29,33 +413,85 @@ impl<T: Hash + Eq + Default> Graph<T> {
         }
         graph
     }
+
+    // Errors with the first duplicate label rather than overwriting
+    pub fn try_init<I: IntoIterator<Item = T>>(labels: I) -> Result<Self, T> {
+        let mut graph = Self::new();
+        for label in labels {
+            if graph.get(&label).is_some() {
+                return Err(label);
+            }
+            graph.add(label);
+        }
+        Ok(graph)
+    }
 }
 
-impl<T: Hash + Eq> Graph<T> {
-    pub(crate) fn get(&self, label: &T) -> Option<&Node<T>> {
+impl<T: Hash + Eq, E> Graph<T, E> {
+    pub(crate) fn get(&self, label: &T) -> Option<&Node<T, E>> {
         let key = hash(label);
         self.nodes.get(&key)
     }
 
+    pub fn node_view(&self, label: &T) -> Option<NodeView<'_, T, E>> {
+        let node = self.get(label)?;
+        Some(NodeView { graph: self, node })
+    }
+
     pub fn add(&mut self, label: T) {
+        self.record(|clone| Op::Add(clone(&label)));
         let key = hash(&label);
         let node = Node {
             label,
-            edges: HashMap::new(),
+            edges: Map::new(),
+            data: Map::new(),
         };
-        self.nodes.insert(key, node);
+        if self.nodes.insert(key, node).is_some() {
+            self.edited_in_bulk(); // overwriting drops the old node's edges
+        } else {
+            self.edited(|e| e.added(key));
+        }
     }
 
-    pub fn remove(&mut self, label: &T) -> Option<Node<T>> {
-        let key = hash(label);
-        let node = self.nodes.remove(&key)?;
+    pub fn remove(&mut self, label: &T) -> Option<Node<T, E>> {
+        self.remove_key(hash(label))
+    }
+
+    pub(crate) fn remove_key(&mut self, key: u64) -> Option<Node<T, E>> {
+        let node = unmap(&mut self.nodes, &key)?;
 
-        for other in self.nodes.values_mut() {
-            other.disconnect_from(label);
+        let mut sources = Vec::new();
+        for (k, other) in self.nodes.iter_mut() {
+            let connected = unmap(&mut other.edges, &key).is_some();
+            unmap(&mut other.data, &key);
+            if connected {
+                sources.push((*k, other.edges.is_empty()));
+            }
         }
+        let targets = node.edges.keys().copied().collect::<Vec<_>>();
+        self.edited(|e| e.removed(key, &targets, &sources));
+        self.record(|clone| Op::Remove(clone(&node.label)));
         Some(node)
     }
 
+    // Removes every node failing keep in one pass over the graph, recording each removal
+    pub(crate) fn retain_keys<F: Fn(&u64) -> bool>(&mut self, keep: F) {
+        if let Some(history) = &mut self.history {
+            for (key, node) in &self.nodes {
+                if !keep(key) {
+                    history.ops.push(Op::Remove((history.clone)(&node.label)));
+                }
+            }
+        }
+
+        self.nodes.retain(|key, _| keep(key));
+        for node in self.nodes.values_mut() {
+            node.edges.retain(|key, _| keep(key));
+            node.data.retain(|key, _| keep(key));
+        }
+        self.edited_in_bulk();
+    }
+
     pub fn connections(&self, label: &T) -> Option<HashSet<&T>> {
         let res = self
             .get(label)?
@@ -73,29 +509,188 @@ impl<T: Hash + Eq> Graph<T> {
         node.is_some() && node.unwrap().is_adjacent_to(to)
     }
 
+    // Connects with weight 1, overwriting the weight and clearing the data of an existing edge
     pub fn connect(&mut self, from: &T, to: &T) -> bool {
-        let a = hash(&from);
-        let b = hash(&to);
-        let bb = self.nodes.contains_key(&b);
-        let na = self.nodes.get_mut(&a);
-        if bb && na.is_some() {
-            na.unwrap().connect_to(to);
-            true
+        self.try_connect(from, to).is_ok()
+    }
+
+    pub fn edge_weight(&self, from: &T, to: &T) -> Option<i64> {
+        self.get(from)?.edges.get(&hash(to)).copied()
+    }
+
+    // Connects if not already, then overwrites the weight. Existing edge data is kept.
+    pub fn set_edge_weight(&mut self, from: &T, to: &T, weight: i64) -> bool {
+        self.update_edge_weight(from, to, |_| weight)
+    }
+
+    // Connects if not already, then sums into the existing weight (0 for a new edge)
+    pub fn add_edge_weight(&mut self, from: &T, to: &T, weight: i64) -> bool {
+        self.update_edge_weight(from, to, |existing| existing + weight)
+    }
+
+    fn update_edge_weight<F: FnOnce(i64) -> i64>(&mut self, from: &T, to: &T, f: F) -> bool {
+        let existing = self.edge_weight(from, to).unwrap_or(0);
+        let data = self
+            .nodes
+            .get_mut(&hash(from))
+            .and_then(|node| unmap(&mut node.data, &hash(to)));
+        let connected = self.connect(from, to);
+        if let Some(data) = data {
+            let node = self.nodes.get_mut(&hash(from)).unwrap();
+            node.data.insert(hash(to), data);
+        }
+        if !connected {
+            return false;
+        }
+        let node = self.nodes.get_mut(&hash(from)).unwrap();
+        let weight = f(existing);
+        node.edges.insert(hash(to), weight);
+        self.record(|clone| Op::SetWeight(clone(from), clone(to), weight));
+        true
+    }
+
+    pub fn connect_owned(&mut self, from: T, to: T) -> bool {
+        self.connect(&from, &to)
+    }
+
+    pub fn can_connect(&self, from: &T, to: &T) -> Result<(), ConnectError> {
+        self.check_missing(from, to)?;
+        self.check_degrees(&[(hash(from), hash(to))])
+    }
+
+    fn check_missing(&self, from: &T, to: &T) -> Result<(), ConnectError> {
+        let from_missing = self.get(from).is_none();
+        let to_missing = self.get(to).is_none();
+        if from_missing || to_missing {
+            Err(ConnectError::MissingNode {
+                from_missing,
+                to_missing,
+            })
         } else {
-            false
+            Ok(())
+        }
+    }
+
+    pub fn try_connect(&mut self, from: &T, to: &T) -> Result<(), ConnectError> {
+        self.can_connect(from, to)?;
+        let node = self.nodes.get_mut(&hash(from)).unwrap();
+        let new = !node.is_adjacent_to(to);
+        node.connect_to(to);
+        if new {
+            self.edited(|e| e.connected(hash(from), hash(to)));
+        }
+        self.record(|clone| Op::Connect(clone(from), clone(to)));
+        Ok(())
+    }
+
+    // Either every edge is connected or, on the first error, none are
+    pub fn connect_all_or_nothing<I: IntoIterator<Item = (T, T)>>(
+        &mut self,
+        edges: I,
+    ) -> Result<(), ConnectError> {
+        let edges = edges.into_iter().collect::<Vec<_>>();
+        for (from, to) in &edges {
+            self.check_missing(from, to)?;
+        }
+        let keys = edges.iter().map(|(a, b)| (hash(a), hash(b)));
+        self.check_degrees(&keys.collect::<Vec<_>>())?;
+        for (from, to) in &edges {
+            self.try_connect(from, to)?;
+        }
+        Ok(())
+    }
+
+    // Replaces every outgoing edge of from, leaving it untouched on error
+    pub fn set_neighbors<'a, I: IntoIterator<Item = &'a T>>(
+        &mut self,
+        from: &T,
+        targets: I,
+    ) -> Result<(), ConnectError>
+    where
+        T: 'a,
+    {
+        let targets = targets.into_iter().collect::<Vec<_>>();
+        for to in &targets {
+            self.check_missing(from, to)?;
+        }
+        if self.get(from).is_none() {
+            return Err(ConnectError::MissingNode {
+                from_missing: true,
+                to_missing: false,
+            });
+        }
+
+        // Existing outgoing edges are replaced so only the new set counts towards the limit
+        let keys = targets.iter().map(hash).collect::<HashSet<_>>();
+        let a = hash(from);
+        let DegreeLimits { max_in, max_out } = self.degree_limits;
+        let over_in = keys.iter().any(|b| {
+            !self.nodes[&a].edges.contains_key(b)
+                && max_in.is_some_and(|max| self.in_degree(*b) >= max)
+        });
+        if over_in || max_out.is_some_and(|max| keys.len() > max) {
+            return Err(ConnectError::DegreeExceeded);
+        }
+
+        let before = self.bulk_checkpoint();
+        let node = self.nodes.get_mut(&hash(from)).unwrap();
+        node.edges.clear();
+        node.data.clear();
+        for to in targets {
+            node.connect_to(to);
         }
+        self.record_bulk(before);
+        self.edited_in_bulk();
+        Ok(())
+    }
+
+    pub fn connect_labeled(&mut self, from: &T, to: &T, data: E) -> bool {
+        let connected = self.connect(from, to);
+        if connected {
+            let node = self.nodes.get_mut(&hash(from)).unwrap();
+            node.data.insert(hash(to), data);
+        }
+        connected
+    }
+
+    pub fn edge_data(&self, from: &T, to: &T) -> Option<&E> {
+        self.get(from)?.data.get(&hash(to))
     }
 
     pub fn disconnect(&mut self, from: &T, to: &T) -> bool {
         let a = hash(&from);
         let b = hash(&to);
         let bb = self.nodes.contains_key(&b);
-        let na = self.nodes.get_mut(&a);
-        if bb && na.is_some() {
-            na.unwrap().disconnect_from(to);
-            true
+        match self.nodes.get_mut(&a) {
+            Some(na) if bb => {
+                if na.is_adjacent_to(to) {
+                    na.disconnect_from(to);
+                    let leaf = na.edges.is_empty();
+                    self.edited(|e| e.disconnected(a, b, leaf));
+                }
+                self.record(|clone| Op::Disconnect(clone(from), clone(to)));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn apply(&mut self, op: Op<T>) -> OpResult {
+        let applied = match op {
+            Op::Add(label) => {
+                self.add(label);
+                true
+            }
+            Op::Remove(label) => self.remove(&label).is_some(),
+            Op::Connect(from, to) => self.connect(&from, &to),
+            Op::SetWeight(from, to, weight) => self.set_edge_weight(&from, &to, weight),
+            Op::Disconnect(from, to) => self.disconnect(&from, &to),
+        };
+
+        if applied {
+            OpResult::Applied
         } else {
-            false
+            OpResult::Ignored
         }
     }
 
@@ -110,15 +705,112 @@ impl<T: Hash + Eq> Graph<T> {
     pub fn bidisconnect(&mut self, a: &T, b: &T) -> bool {
         self.disconnect(a, b) && self.disconnect(b, a)
     }
+
+    // Leaves the graph untouched if two labels would map to the same new label
+    pub fn relabel<F: FnMut(&T) -> T>(&mut self, mut f: F) -> Result<(), RelabelError> {
+        let mut labels = Vec::with_capacity(self.nodes.len());
+        let mut rekeyed = HashMap::new();
+        let mut new_keys = HashSet::new();
+        for (key, node) in &self.nodes {
+            let label = f(&node.label);
+            let new_key = hash(&label);
+            if !new_keys.insert(new_key) {
+                return Err(RelabelError::Collision);
+            }
+            rekeyed.insert(*key, new_key);
+            labels.push(label);
+        }
+
+        let before = self.bulk_checkpoint();
+        let nodes = mem::take(&mut self.nodes);
+        for (node, label) in nodes.into_values().zip(labels) {
+            let node = Node {
+                label,
+                edges: node
+                    .edges
+                    .into_iter()
+                    .map(|(k, w)| (rekeyed[&k], w))
+                    .collect(),
+                data: node
+                    .data
+                    .into_iter()
+                    .map(|(k, d)| (rekeyed[&k], d))
+                    .collect(),
+            };
+            self.nodes.insert(hash(&node.label), node);
+        }
+        self.record_bulk(before);
+        self.edited_in_bulk();
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
-pub struct Node<T> {
+#[derive(Debug, Clone)]
+pub struct Snapshot<T, E = ()>(Graph<T, E>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<T> {
+    Add(T),
+    Remove(T),
+    Connect(T, T),
+    Disconnect(T, T),
+    SetWeight(T, T, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpResult {
+    Applied,
+    Ignored, // e.g. removing or connecting a missing node
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConnectError {
+    MissingNode {
+        from_missing: bool,
+        to_missing: bool,
+    },
+    DegreeExceeded, // a node would pass its maximum in or out degree
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectError::MissingNode {
+                from_missing,
+                to_missing,
+            } => match (from_missing, to_missing) {
+                (true, true) => write!(f, "missing both nodes"),
+                (true, false) => write!(f, "missing from node"),
+                _ => write!(f, "missing to node"),
+            },
+            ConnectError::DegreeExceeded => write!(f, "node would exceed its degree limit"),
+        }
+    }
+}
+
+impl Error for ConnectError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RelabelError {
+    Collision,
+}
+
+impl fmt::Display for RelabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "relabelling maps two labels to the same label")
+    }
+}
+
+impl Error for RelabelError {}
+
+#[derive(Debug, Clone)]
+pub struct Node<T, E = ()> {
     pub label: T,
-    pub(crate) edges: HashMap<u64, i64>, // key is target, value is weight
+    pub(crate) edges: Map<u64, i64>, // key is target, value is weight
+    pub(crate) data: Map<u64, E>,    // only for edges connected with data
 }
 
-impl<T: Hash> Node<T> {
+impl<T: Hash, E> Node<T, E> {
     pub fn is_adjacent_to(&self, to: &T) -> bool {
         let target = hash(to);
         self.edges.contains_key(&target)
@@ -127,11 +819,32 @@ impl<T: Hash> Node<T> {
     pub fn connect_to(&mut self, to: &T) {
         let target = hash(to);
         self.edges.insert(target, 1);
+        unmap(&mut self.data, &target);
     }
 
     pub fn disconnect_from(&mut self, from: &T) {
         let target = hash(from);
-        self.edges.remove(&target);
+        unmap(&mut self.edges, &target);
+        unmap(&mut self.data, &target);
+    }
+}
+
+pub struct NodeView<'a, T, E = ()> {
+    graph: &'a Graph<T, E>,
+    node: &'a Node<T, E>,
+}
+
+impl<'a, T, E> NodeView<'a, T, E> {
+    pub fn label(&self) -> &'a T {
+        &self.node.label
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = (&'a T, i64)> + 'a {
+        let graph = self.graph;
+        self.node
+            .edges
+            .iter()
+            .map(move |(k, w)| (&graph.nodes.get(k).unwrap().label, *w))
     }
 }
 
@@ -166,4 +879,333 @@ mod tests {
         assert!(g.connections(&'b').unwrap().is_empty());
         assert!(g.connections(&'c').unwrap().is_empty());
     }
+
+    #[test]
+    fn try_connect() {
+        let mut g = Graph::init('a'..='c');
+        assert_eq!(g.try_connect(&'a', &'b'), Ok(()));
+        assert!(g.is_connected(&'a', &'b'));
+
+        assert_eq!(
+            g.try_connect(&'a', &'d'),
+            Err(ConnectError::MissingNode {
+                from_missing: false,
+                to_missing: true
+            })
+        );
+        assert_eq!(
+            g.try_connect(&'d', &'e'),
+            Err(ConnectError::MissingNode {
+                from_missing: true,
+                to_missing: true
+            })
+        );
+    }
+
+    #[test]
+    fn node_view() {
+        let mut g = Graph::init('a'..='c');
+
+        // b <- a -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+
+        let view = g.node_view(&'a').unwrap();
+        assert_eq!(view.label(), &'a');
+
+        let targets = view.targets().map(|(t, _)| t).collect::<HashSet<_>>();
+        assert_eq!(targets, g.connections(&'a').unwrap());
+        assert!(g.node_view(&'d').is_none());
+    }
+
+    #[test]
+    fn apply() {
+        let mut g = Graph::new();
+
+        assert_eq!(g.apply(Op::Add('a')), OpResult::Applied);
+        assert_eq!(g.apply(Op::Add('b')), OpResult::Applied);
+        assert_eq!(g.apply(Op::Connect('a', 'b')), OpResult::Applied);
+        assert_eq!(g.apply(Op::Connect('a', 'c')), OpResult::Ignored);
+        assert!(g.is_connected(&'a', &'b'));
+
+        assert_eq!(g.apply(Op::Disconnect('a', 'b')), OpResult::Applied);
+        assert!(!g.is_connected(&'a', &'b'));
+
+        assert_eq!(g.apply(Op::Remove('b')), OpResult::Applied);
+        assert_eq!(g.apply(Op::Remove('b')), OpResult::Ignored);
+    }
+
+    #[test]
+    fn connect_owned() {
+        let mut g = Graph::init(vec![String::from("a"), String::from("b")]);
+
+        let (from, to) = (String::from("a"), String::from("b"));
+        assert!(g.connect_owned(from, to));
+        assert!(g.is_connected(&String::from("a"), &String::from("b")));
+        assert!(!g.connect_owned(String::from("a"), String::from("c")));
+    }
+
+    #[test]
+    fn edge_data() {
+        let mut g: Graph<char, &str> = Graph::default();
+        g.add('a');
+        g.add('b');
+        g.add('c');
+
+        assert!(g.connect_labeled(&'a', &'b', "build"));
+        assert!(g.connect(&'a', &'c'));
+        assert!(!g.connect_labeled(&'a', &'d', "test"));
+
+        assert_eq!(g.edge_data(&'a', &'b'), Some(&"build"));
+        assert_eq!(g.edge_data(&'a', &'c'), None);
+
+        assert!(g.set_edge_weight(&'a', &'b', 3));
+        assert_eq!(g.edge_data(&'a', &'b'), Some(&"build"));
+
+        assert!(g.connect(&'a', &'b'));
+        assert_eq!(g.edge_data(&'a', &'b'), None);
+        assert_eq!(g.edge_weight(&'a', &'b'), Some(1));
+
+        assert!(g.connect_labeled(&'a', &'b', "build"));
+        assert!(g.disconnect(&'a', &'b'));
+        assert_eq!(g.edge_data(&'a', &'b'), None);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut g = Graph::init('a'..='c');
+        assert!(g.connect(&'a', &'b'));
+
+        let snapshot = g.snapshot();
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.remove(&'a').is_some());
+
+        g.restore(snapshot);
+        assert!(g.is_connected(&'a', &'b'));
+        assert!(!g.is_connected(&'b', &'c'));
+    }
+
+    #[test]
+    fn edge_weights() {
+        let mut g = Graph::init('a'..='c');
+
+        assert!(g.connect(&'a', &'b'));
+        assert_eq!(g.edge_weight(&'a', &'b'), Some(1));
+
+        assert!(g.set_edge_weight(&'a', &'b', 5));
+        assert_eq!(g.edge_weight(&'a', &'b'), Some(5));
+
+        assert!(g.add_edge_weight(&'a', &'b', 2));
+        assert_eq!(g.edge_weight(&'a', &'b'), Some(7));
+
+        assert!(g.add_edge_weight(&'a', &'c', 3));
+        assert_eq!(g.edge_weight(&'a', &'c'), Some(3));
+
+        assert!(g.connect(&'a', &'b'));
+        assert_eq!(g.edge_weight(&'a', &'b'), Some(1));
+
+        assert!(!g.set_edge_weight(&'a', &'d', 1));
+        assert_eq!(g.edge_weight(&'b', &'c'), None);
+    }
+
+    #[test]
+    fn label_for_key() {
+        let g = Graph::init('a'..='b');
+        assert_eq!(g.label_for_key(hash(&'a')), Some(&'a'));
+        assert_eq!(g.label_for_key(hash(&'c')), None);
+    }
+
+    #[test]
+    fn try_init() {
+        assert!(Graph::try_init('a'..='c').is_ok());
+        assert_eq!(Graph::try_init(vec!['a', 'b', 'a', 'c']).unwrap_err(), 'a');
+    }
+
+    #[test]
+    fn connect_all_or_nothing() {
+        let mut g = Graph::init('a'..='c');
+
+        let res = g.connect_all_or_nothing(vec![('a', 'b'), ('b', 'd'), ('a', 'c')]);
+        assert!(res.is_err());
+        assert_eq!(g.edges().count(), 0);
+
+        assert!(g
+            .connect_all_or_nothing(vec![('a', 'b'), ('a', 'c')])
+            .is_ok());
+        assert_eq!(g.edges().count(), 2);
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn insertion_order() {
+        let mut g = Graph::init(vec!['d', 'a', 'c', 'b']);
+        assert!(g.remove(&'a').is_some());
+        g.add('a');
+
+        assert_eq!(
+            g.into_labels().collect::<Vec<_>>(),
+            vec!['d', 'c', 'b', 'a']
+        );
+    }
+
+    #[test]
+    fn set_neighbors() {
+        let mut g = Graph::init('a'..='d');
+        assert!(g.connect(&'a', &'b'));
+
+        assert!(g.set_neighbors(&'a', &['c', 'e']).is_err());
+        assert!(g.is_connected(&'a', &'b'));
+
+        assert!(g.set_neighbors(&'a', &['c', 'd']).is_ok());
+        assert!(!g.is_connected(&'a', &'b'));
+        assert!(g.is_connected(&'a', &'c'));
+        assert!(g.is_connected(&'a', &'d'));
+
+        assert!(g.set_neighbors(&'e', &[]).is_err());
+    }
+
+    #[test]
+    fn relabel() {
+        let mut g = Graph::init(vec![String::from("a"), String::from("b")]);
+        assert!(g.connect(&String::from("a"), &String::from("b")));
+
+        assert_eq!(
+            g.relabel(|_| String::from("x")),
+            Err(RelabelError::Collision)
+        );
+        assert!(g.is_connected(&String::from("a"), &String::from("b")));
+
+        assert!(g.relabel(|l| l.to_uppercase()).is_ok());
+        assert!(g.is_connected(&String::from("A"), &String::from("B")));
+        assert!(g.connections(&String::from("a")).is_none());
+    }
+
+    #[test]
+    fn history() {
+        let mut g = Graph::init('a'..='b').with_history();
+
+        g.add('c');
+        assert!(g.connect(&'a', &'c'));
+        assert!(!g.connect(&'a', &'d'));
+        assert!(g.disconnect(&'a', &'c'));
+        assert!(g.remove(&'b').is_some());
+        assert_eq!(
+            g.history(),
+            &[
+                Op::Add('c'),
+                Op::Connect('a', 'c'),
+                Op::Disconnect('a', 'c'),
+                Op::Remove('b')
+            ]
+        );
+
+        let mut h = Graph::init('a'..='b');
+        for op in g.history().iter().cloned() {
+            h.apply(op);
+        }
+        assert_eq!(h.nodes.len(), 2);
+        assert!(h.connections(&'c').unwrap().is_empty());
+
+        g.clear_history();
+        assert!(g.history().is_empty());
+        assert!(Graph::<char>::new().history().is_empty());
+    }
+
+    #[test]
+    fn history_replay() {
+        fn replayed(g: &Graph<char>) -> Graph<char> {
+            let mut h = Graph::init('a'..='f');
+            for op in g.history().iter().cloned() {
+                assert_eq!(h.apply(op), OpResult::Applied);
+            }
+            h
+        }
+        fn edges(g: &Graph<char>) -> HashSet<(char, char, i64)> {
+            g.edges().map(|e| (*e.from, *e.to, e.weight)).collect()
+        }
+
+        let mut g = Graph::init('a'..='f').with_history();
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.set_edge_weight(&'a', &'b', 5));
+        assert_eq!(g.set_neighbors(&'a', &['c']), Ok(()));
+        assert_eq!(edges(&replayed(&g)), [('a', 'c', 1)].into());
+
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.add_edge_weight(&'c', &'d', 2));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.set_edge_weight(&'e', &'f', 4));
+        assert!(g.connect(&'b', &'b'));
+        assert_eq!(g.collapse_chains(), 3);
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.contract_edge(&'a', &'b'));
+        g.merge_duplicates_by(|x, y| x == y);
+        assert_eq!(g.relabel(|label| label.to_ascii_uppercase()), Ok(()));
+        g.retain_reachable_from(&['A']);
+
+        let h = replayed(&g);
+        assert_eq!(edges(&g), [('A', 'F', 4)].into());
+        assert_eq!(edges(&h), edges(&g));
+        assert_eq!(
+            h.into_labels().collect::<HashSet<_>>(),
+            g.into_labels().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn cached_endpoints() {
+        fn assert_fresh(g: &Graph<char>) {
+            let roots = g.cached_roots().unwrap().collect::<HashSet<_>>();
+            let leaves = g.cached_leaves().unwrap().collect::<HashSet<_>>();
+            assert_eq!(roots, g.roots());
+            assert_eq!(leaves, g.leaves());
+        }
+
+        let mut g = Graph::init('a'..='c');
+        assert!(g.cached_roots().is_none());
+        assert!(g.connect(&'a', &'b'));
+        let mut g = g.with_endpoint_tracking();
+        assert_fresh(&g);
+
+        // a -> b -> c
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'b', &'c'));
+        assert_fresh(&g);
+        assert_eq!(g.cached_roots().unwrap().count(), 1);
+
+        assert!(g.disconnect(&'a', &'b'));
+        assert!(g.disconnect(&'a', &'b'));
+        assert_fresh(&g);
+
+        g.add('d');
+        assert!(g.connect(&'d', &'b'));
+        assert!(g.connect(&'c', &'c'));
+        assert_fresh(&g);
+
+        assert!(g.remove(&'b').is_some());
+        assert_fresh(&g);
+        assert!(g.remove(&'c').is_some());
+        assert_fresh(&g);
+
+        g.add('d');
+        assert!(g.set_neighbors(&'a', &['d']).is_ok());
+        assert_fresh(&g);
+    }
+
+    #[test]
+    fn degree_limits() {
+        let mut g = Graph::init('a'..='d').max_out_degree(2).max_in_degree(1);
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'a', &'c'));
+        assert_eq!(g.try_connect(&'a', &'d'), Err(ConnectError::DegreeExceeded));
+        assert_eq!(g.try_connect(&'d', &'b'), Err(ConnectError::DegreeExceeded));
+
+        let res = g.connect_all_or_nothing(vec![('b', 'd'), ('c', 'd')]);
+        assert_eq!(res, Err(ConnectError::DegreeExceeded));
+        assert_eq!(g.edges().count(), 2);
+
+        assert!(g.set_neighbors(&'a', &['b', 'd']).is_ok());
+        assert!(g.set_neighbors(&'a', &['b', 'c', 'd']).is_err());
+        assert!(g.set_neighbors(&'c', &['d']).is_err());
+    }
 }
@@ -1,103 +1,288 @@
 use crate::{graph::*, hash};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::Hash;
+use std::ops::ControlFlow;
 
 pub enum Mode {
     Bredth,
     Depth,
+    DepthLimited(usize), // depth first, never more than this many edges from the start
 }
 
-impl<T: Hash> Graph<T> {
-    pub fn bfs<'a>(&'a self, start: &'a T) -> WalkIter<'a, T> {
+// Stateful traversal driven by accept, which stops as soon as visit breaks
+pub trait Visitor<T> {
+    fn visit(&mut self, node: &T) -> ControlFlow<()>;
+}
+
+impl<T: Hash, E> Graph<T, E> {
+    pub fn bfs<'a>(&'a self, start: &'a T) -> WalkIter<'a, T, E> {
         self.walk(start, Mode::Bredth)
     }
 
-    pub fn dfs<'a>(&'a self, start: &'a T) -> WalkIter<'a, T> {
+    pub fn dfs<'a>(&'a self, start: &'a T) -> WalkIter<'a, T, E> {
         self.walk(start, Mode::Depth)
     }
 
-    pub fn walk<'a>(&'a self, start: &'a T, mode: Mode) -> WalkIter<'a, T> {
+    pub fn walk<'a>(&'a self, start: &'a T, mode: Mode) -> WalkIter<'a, T, E> {
         let mut buffer = VecDeque::new();
-        buffer.push_front(start);
+        buffer.push_front((start, 0, None));
 
         let mut visited = HashSet::new();
-        visited.insert(hash(start));
+        let mut depths = HashMap::new();
+        match mode {
+            Mode::DepthLimited(_) => {
+                depths.insert(hash(start), 0);
+            }
+            _ => {
+                visited.insert(hash(start));
+            }
+        }
         WalkIter {
             mode,
             buffer,
             visited,
-            graph: &self,
+            depths,
+            graph: self,
         }
     }
 
-    pub fn edges<'a>(&'a self) -> EdgeIter<'a, T> {
+    // Pairs each node with the node it was first discovered from, None for the start.
+    // For bredth first this is the shortest path parent, for depth first it is whichever
+    // node first reached it, which may not be the node it was popped beneath.
+    pub fn walk_with_parents<'a>(&'a self, start: &'a T, mode: Mode) -> ParentWalkIter<'a, T, E> {
+        ParentWalkIter(self.walk(start, mode))
+    }
+
+    // Only follows an edge from current to neighbor if allow(current, neighbor)
+    pub fn walk_filtered<'a, F>(
+        &'a self,
+        start: &'a T,
+        mode: Mode,
+        allow: F,
+    ) -> FilteredWalkIter<'a, T, F, E>
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        FilteredWalkIter {
+            walk: self.walk(start, mode),
+            allow,
+        }
+    }
+
+    pub fn edges<'a>(&'a self) -> EdgeIter<'a, T, E> {
         EdgeIter {
-            graph: &self,
+            graph: self,
             nodes: self.nodes.values().collect(),
-            edges: Vec::new(),
+            edges: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    // Does nothing if start is not in the graph
+    pub fn accept<V: Visitor<T>>(&self, start: &T, mode: Mode, visitor: &mut V) {
+        let start = match self.get(start) {
+            Some(node) => &node.label,
+            None => return,
+        };
+        for node in self.walk(start, mode) {
+            if visitor.visit(node).is_break() {
+                return;
+            }
+        }
+    }
+}
+
+impl<T, E> Graph<T, E> {
+    pub fn into_labels(self) -> impl Iterator<Item = T> {
+        self.nodes.into_values().map(|node| node.label)
+    }
+}
+
+impl<T: Clone, E> Graph<T, E> {
+    pub fn into_edges(self) -> Vec<(T, T)> {
+        let mut edges = Vec::new();
+        for node in self.nodes.values() {
+            for target in node.edges.keys() {
+                let to = &self.nodes[target].label;
+                edges.push((node.label.clone(), to.clone()));
+            }
         }
+        edges
     }
 }
 
-pub struct WalkIter<'a, T> {
+impl<T: Clone + Eq + Hash, E> Graph<T, E> {
+    pub fn to_adjacency_map(&self) -> HashMap<T, Vec<T>> {
+        let mut map = HashMap::new();
+        for node in self.nodes.values() {
+            let targets = node
+                .edges
+                .keys()
+                .map(|k| self.nodes[k].label.clone())
+                .collect();
+            map.insert(node.label.clone(), targets);
+        }
+        map
+    }
+}
+
+impl<T, E> Graph<T, E> {
+    // Labels by index alongside each index's neighbor indices, free of the internal keys
+    pub fn to_index_adjacency(&self) -> (Vec<&T>, Vec<Vec<usize>>) {
+        let index: HashMap<u64, usize> = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(i, k)| (*k, i))
+            .collect();
+        let labels = self.nodes.values().map(|node| &node.label).collect();
+        let adjacency = self
+            .nodes
+            .values()
+            .map(|node| node.edges.keys().map(|k| index[k]).collect())
+            .collect();
+        (labels, adjacency)
+    }
+}
+
+pub struct WalkIter<'a, T, E = ()> {
     mode: Mode,
-    graph: &'a Graph<T>,
-    buffer: VecDeque<&'a T>,
-    visited: HashSet<u64>,
+    graph: &'a Graph<T, E>,
+    buffer: VecDeque<(&'a T, usize, Option<&'a T>)>,
+    visited: HashSet<u64>, // pushed, or when depth limited already yielded
+    depths: HashMap<u64, usize>, // shallowest depth pushed, only when depth limited
 }
 
-impl<'a, T: Hash + Eq> Iterator for WalkIter<'a, T> {
-    type Item = &'a T;
+impl<'a, T: Hash + Eq, E> WalkIter<'a, T, E> {
+    fn step(&mut self) -> Option<(&'a T, Option<&'a T>)> {
+        self.step_allowing(|_, _| true)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = match self.mode {
-            Mode::Bredth => self.buffer.pop_back()?,
-            Mode::Depth => self.buffer.pop_front()?,
-        };
+    // Only follows edges which are allowed. Depth limited walks push a node again whenever
+    // a shallower path to it turns up, so it is expanded as far as the limit allows, but
+    // only yield it the first time it is popped.
+    fn step_allowing<F: Fn(&T, &T) -> bool>(&mut self, allow: F) -> Option<(&'a T, Option<&'a T>)> {
+        loop {
+            let (next, depth, parent) = match self.mode {
+                Mode::Bredth => self.buffer.pop_back()?,
+                Mode::Depth | Mode::DepthLimited(_) => self.buffer.pop_front()?,
+            };
+
+            let mut first = true;
+            if let Mode::DepthLimited(limit) = self.mode {
+                let key = hash(next);
+                if self.depths[&key] < depth {
+                    continue; // superseded by a shallower push
+                }
+                first = self.visited.insert(key);
+                if depth >= limit {
+                    if first {
+                        return Some((next, parent));
+                    }
+                    continue;
+                }
+            }
 
-        if let Some(connections) = self.graph.connections(next) {
-            for connection in connections {
-                let key = hash(connection);
-                if !self.visited.contains(&key) {
-                    self.visited.insert(key);
-                    self.buffer.push_front(connection);
+            if let Some(node) = self.graph.get(next) {
+                // Depth first pops the most recent push, so targets go in backwards for the
+                // first edge to be walked first
+                let mut targets = node.edges.keys().collect::<Vec<_>>();
+                if !matches!(self.mode, Mode::Bredth) {
+                    targets.reverse();
+                }
+                for key in targets {
+                    let connection = &self.graph.nodes[key].label;
+                    let unseen = match self.mode {
+                        Mode::DepthLimited(_) => {
+                            self.depths.get(key).is_none_or(|d| depth + 1 < *d)
+                        }
+                        _ => !self.visited.contains(key),
+                    };
+                    if unseen && allow(next, connection) {
+                        match self.mode {
+                            Mode::DepthLimited(_) => {
+                                self.depths.insert(*key, depth + 1);
+                            }
+                            _ => {
+                                self.visited.insert(*key);
+                            }
+                        }
+                        self.buffer.push_front((connection, depth + 1, Some(next)));
+                    }
                 }
             }
+            if first {
+                return Some((next, parent));
+            }
         }
-        Some(next)
     }
 }
 
-pub struct Edge<'a, T> {
+impl<'a, T: Hash + Eq, E> Iterator for WalkIter<'a, T, E> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step().map(|(next, _)| next)
+    }
+}
+
+pub struct FilteredWalkIter<'a, T, F, E = ()> {
+    walk: WalkIter<'a, T, E>,
+    allow: F,
+}
+
+impl<'a, T: Hash + Eq, F: Fn(&T, &T) -> bool, E> Iterator for FilteredWalkIter<'a, T, F, E> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.walk.step_allowing(&self.allow).map(|(next, _)| next)
+    }
+}
+
+pub struct ParentWalkIter<'a, T, E = ()>(WalkIter<'a, T, E>);
+
+impl<'a, T: Hash + Eq, E> Iterator for ParentWalkIter<'a, T, E> {
+    type Item = (&'a T, Option<&'a T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.step()
+    }
+}
+
+pub struct Edge<'a, T, E = ()> {
     pub from: &'a T,
     pub to: &'a T,
     pub weight: i64,
+    pub data: Option<&'a E>,
 }
 
-pub struct EdgeIter<'a, T> {
-    graph: &'a Graph<T>,
-    nodes: Vec<&'a Node<T>>,
-    edges: Vec<Edge<'a, T>>,
+pub struct EdgeIter<'a, T, E = ()> {
+    graph: &'a Graph<T, E>,
+    nodes: VecDeque<&'a Node<T, E>>,
+    edges: VecDeque<Edge<'a, T, E>>,
 }
 
-impl<'a, T> Iterator for EdgeIter<'a, T> {
-    type Item = Edge<'a, T>;
+impl<'a, T, E> Iterator for EdgeIter<'a, T, E> {
+    type Item = Edge<'a, T, E>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(edge) = self.edges.pop() {
-            return Some(edge);
-        }
+        loop {
+            if let Some(edge) = self.edges.pop_front() {
+                return Some(edge);
+            }
 
-        let from = self.nodes.pop()?;
-        for edge in &from.edges {
-            let to = self.graph.nodes.get(edge.0).unwrap();
-            self.edges.push(Edge {
-                from: &from.label,
-                to: &to.label,
-                weight: edge.1.to_owned(),
-            });
+            let from = self.nodes.pop_front()?;
+            for edge in &from.edges {
+                let to = self.graph.nodes.get(edge.0).unwrap();
+                self.edges.push_back(Edge {
+                    from: &from.label,
+                    to: &to.label,
+                    weight: edge.1.to_owned(),
+                    data: from.data.get(edge.0),
+                });
+            }
         }
-        self.next()
     }
 }
 
@@ -168,6 +353,67 @@ mod tests {
         assert_eq!(depth.len(), 3); // Only visit each once
     }
 
+    #[test]
+    fn depth_limited() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        let shallow = g.walk(&'a', Mode::DepthLimited(2)).collect::<Vec<_>>();
+        assert_eq!(shallow, vec![&'a', &'b', &'c']);
+
+        let start = g.walk(&'a', Mode::DepthLimited(0)).collect::<Vec<_>>();
+        assert_eq!(start, vec![&'a']);
+    }
+
+    #[test]
+    fn walk_with_parents() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> d
+        // a -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+
+        let walk = g.walk_with_parents(&'a', Mode::Bredth).collect::<Vec<_>>();
+        assert_eq!(walk.len(), 4);
+        assert_eq!(walk[0], (&'a', None));
+        for (node, parent) in &walk[1..] {
+            assert!(g.is_connected(parent.unwrap(), node));
+        }
+    }
+
+    #[test]
+    fn to_adjacency_map() {
+        let mut g = Graph::init('a'..='c');
+        assert!(g.connect(&'a', &'b'));
+
+        let map = g.to_adjacency_map();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&'a'], vec!['b']);
+        assert!(map[&'c'].is_empty());
+    }
+
+    #[test]
+    fn walk_filtered() {
+        let mut g = Graph::init(vec!["app", "lib", "test-util", "test-data"]);
+
+        // app -> lib -> test-util -> test-data
+        assert!(g.connect(&"app", &"lib"));
+        assert!(g.connect(&"lib", &"test-util"));
+        assert!(g.connect(&"test-util", &"test-data"));
+
+        let walk = g
+            .walk_filtered(&"app", Mode::Depth, |_, to| !to.starts_with("test"))
+            .collect::<Vec<_>>();
+        assert_eq!(walk, vec![&"app", &"lib"]);
+    }
+
     #[test]
     fn edges() {
         let mut g = Graph::init('a'..='f');
@@ -180,4 +426,125 @@ mod tests {
 
         assert_eq!(g.edges().count(), 4)
     }
+
+    #[test]
+    fn into_labels_and_edges() {
+        let mut g = Graph::init('a'..='c');
+
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+
+        let mut edges = g.into_edges();
+        edges.sort();
+        assert_eq!(edges, vec![('a', 'b'), ('a', 'c')]);
+
+        let mut labels = Graph::init('a'..='c').into_labels().collect::<Vec<_>>();
+        labels.sort();
+        assert_eq!(labels, vec!['a', 'b', 'c']);
+    }
+
+    #[test]
+    fn accept_visitor() {
+        struct Until(char, Vec<char>);
+        impl Visitor<char> for Until {
+            fn visit(&mut self, node: &char) -> ControlFlow<()> {
+                self.1.push(*node);
+                if *node == self.0 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+        }
+
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        let mut visitor = Until('c', Vec::new());
+        g.accept(&'a', Mode::Depth, &mut visitor);
+        assert_eq!(visitor.1, vec!['a', 'b', 'c']);
+
+        let mut visitor = Until('c', Vec::new());
+        g.accept(&'e', Mode::Depth, &mut visitor);
+        assert!(visitor.1.is_empty());
+    }
+
+    #[test]
+    fn to_index_adjacency() {
+        let mut g = Graph::init('a'..='c');
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'c'));
+
+        let (labels, adjacency) = g.to_index_adjacency();
+        assert_eq!(labels.len(), 3);
+        assert_eq!(adjacency.len(), 3);
+        for (i, targets) in adjacency.iter().enumerate() {
+            for j in targets {
+                assert!(g.is_connected(labels[i], labels[*j]));
+            }
+        }
+        assert_eq!(adjacency.iter().map(|t| t.len()).sum::<usize>(), 3);
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn insertion_order_walks() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> d -> f
+        // a -> b
+        // a -> c -> e
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'d', &'f'));
+        assert!(g.connect(&'c', &'e'));
+
+        let bredth = g.bfs(&'a').collect::<Vec<_>>();
+        assert_eq!(bredth, vec![&'a', &'d', &'b', &'c', &'f', &'e']);
+        let depth = g.dfs(&'a').collect::<Vec<_>>();
+        assert_eq!(depth, vec![&'a', &'d', &'f', &'b', &'c', &'e']);
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn insertion_order_edges() {
+        let mut g = Graph::init(vec!['c', 'a', 'b']);
+        assert!(g.connect(&'c', &'b'));
+        assert!(g.connect(&'c', &'a'));
+        assert!(g.connect(&'a', &'c'));
+
+        let edges = g.edges().map(|e| (*e.from, *e.to)).collect::<Vec<_>>();
+        assert_eq!(edges, vec![('c', 'b'), ('c', 'a'), ('a', 'c')]);
+    }
+
+    #[test]
+    fn depth_limited_shorter_path_found_later() {
+        // Iteration order varies between graphs, so build several to try different orders
+        for _ in 0..20 {
+            let mut g = Graph::init('a'..='f');
+
+            // a -> b -> c -> d -> f
+            // a -> e -> d
+            assert!(g.connect(&'a', &'b'));
+            assert!(g.connect(&'b', &'c'));
+            assert!(g.connect(&'c', &'d'));
+            assert!(g.connect(&'d', &'f'));
+            assert!(g.connect(&'a', &'e'));
+            assert!(g.connect(&'e', &'d'));
+
+            let mut walk = g.walk(&'a', Mode::DepthLimited(3)).collect::<Vec<_>>();
+            walk.sort();
+            assert_eq!(walk, vec![&'a', &'b', &'c', &'d', &'e', &'f']);
+
+            let mut walk = g.walk(&'a', Mode::DepthLimited(2)).collect::<Vec<_>>();
+            walk.sort();
+            assert_eq!(walk, vec![&'a', &'b', &'c', &'d', &'e']);
+        }
+    }
 }
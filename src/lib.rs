@@ -1,10 +1,34 @@
+pub mod dominator;
 pub mod draw;
 pub mod graph;
 pub mod iter;
+pub mod load;
+pub mod ops;
+pub mod path;
+pub mod query;
+pub mod reach;
+pub mod sort;
+pub mod validate;
 
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
+// Insertion ordered with the "ordered" feature so iteration is reproducible
+#[cfg(feature = "ordered")]
+pub(crate) type Map<K, V> = indexmap::IndexMap<K, V>;
+#[cfg(not(feature = "ordered"))]
+pub(crate) type Map<K, V> = std::collections::HashMap<K, V>;
+
+#[cfg(feature = "ordered")]
+fn unmap<V>(map: &mut Map<u64, V>, key: &u64) -> Option<V> {
+    map.shift_remove(key)
+}
+
+#[cfg(not(feature = "ordered"))]
+fn unmap<V>(map: &mut Map<u64, V>, key: &u64) -> Option<V> {
+    map.remove(key)
+}
+
 fn hash<T: Hash>(data: &T) -> u64 {
     let mut hasher = DefaultHasher::new();
     data.hash(&mut hasher);
@@ -0,0 +1,107 @@
+use crate::{graph::*, hash};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Malformed(Vec<usize>), // line numbers, starting from 1
+    Cyclic(Vec<usize>),    // well formed lines whose edge would close a cycle
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "failed to read edges: {}", e),
+            LoadError::Malformed(lines) => write!(f, "malformed edges on lines {:?}", lines),
+            LoadError::Cyclic(lines) => write!(f, "edges closing a cycle on lines {:?}", lines),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl Graph<String> {
+    // One from<TAB>to pair per line, blank lines are skipped. Malformed lines are reported
+    // ahead of lines which would close a cycle, which are never connected.
+    pub fn from_edge_reader<R: BufRead>(reader: R) -> Result<Graph<String>, LoadError> {
+        let mut graph = Graph::new();
+        let mut malformed = Vec::new();
+        let mut cyclic = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<_>>();
+            match fields[..] {
+                [from, to] if !from.is_empty() && !to.is_empty() => {
+                    let (from, to) = (from.to_string(), to.to_string());
+                    if graph.get(&from).is_none() {
+                        graph.add(from.clone());
+                    }
+                    if graph.get(&to).is_none() {
+                        graph.add(to.clone());
+                    }
+                    if graph.path_keys(hash(&to), hash(&from), None).is_some() {
+                        cyclic.push(i + 1);
+                    } else {
+                        graph.connect_owned(from, to);
+                    }
+                }
+                _ => malformed.push(i + 1),
+            }
+        }
+
+        if !malformed.is_empty() {
+            Err(LoadError::Malformed(malformed))
+        } else if !cyclic.is_empty() {
+            Err(LoadError::Cyclic(cyclic))
+        } else {
+            Ok(graph)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_edge_reader() {
+        let input = "a\tb\nb\tc\n\na\tc\n";
+        let g = Graph::from_edge_reader(input.as_bytes()).unwrap();
+        assert!(g.is_connected(&"a".to_string(), &"b".to_string()));
+        assert!(g.is_connected(&"b".to_string(), &"c".to_string()));
+        assert_eq!(g.edges().count(), 3);
+
+        let input = "a\tb\nc\nd\te\tf\n\tg\n";
+        match Graph::from_edge_reader(input.as_bytes()) {
+            Err(LoadError::Malformed(lines)) => assert_eq!(lines, vec![2, 3, 4]),
+            _ => panic!("expected malformed lines"),
+        }
+    }
+
+    #[test]
+    fn from_edge_reader_cycles() {
+        let input = "a\tb\nb\tc\nc\ta\nd\td\nc\td\n";
+        match Graph::from_edge_reader(input.as_bytes()) {
+            Err(LoadError::Cyclic(lines)) => assert_eq!(lines, vec![3, 4]),
+            _ => panic!("expected cyclic lines"),
+        }
+
+        let input = "a\tb\nb\ta\nc\n";
+        match Graph::from_edge_reader(input.as_bytes()) {
+            Err(LoadError::Malformed(lines)) => assert_eq!(lines, vec![3]),
+            _ => panic!("expected malformed lines"),
+        }
+    }
+}
@@ -0,0 +1,851 @@
+use crate::{graph::*, hash, unmap, Map};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::mem;
+
+impl<T: Hash + Eq + Clone, E: Clone> Graph<T, E> {
+    pub fn intersection(&self, other: &Graph<T, E>) -> Graph<T, E> {
+        self.subgraph(
+            |key| other.nodes.contains_key(&key),
+            |key, target| other.nodes[&key].edges.contains_key(&target),
+        )
+    }
+
+    // Only the given nodes and the edges between them
+    pub(crate) fn induced(&self, keys: &HashSet<u64>) -> Graph<T, E> {
+        self.subgraph(
+            |key| keys.contains(&key),
+            |_, target| keys.contains(&target),
+        )
+    }
+
+    // Copies the kept nodes with their kept edges, where an edge is only considered when
+    // both its ends are kept
+    fn subgraph<N, K>(&self, keep_node: N, keep_edge: K) -> Graph<T, E>
+    where
+        N: Fn(u64) -> bool,
+        K: Fn(u64, u64) -> bool,
+    {
+        let mut nodes = Map::new();
+        for (key, node) in self.nodes.iter().filter(|(key, _)| keep_node(**key)) {
+            let edges: Map<_, _> = node
+                .edges
+                .iter()
+                .filter(|(target, _)| keep_node(**target) && keep_edge(*key, **target))
+                .map(|(target, weight)| (*target, *weight))
+                .collect();
+
+            let data = node
+                .data
+                .iter()
+                .filter(|(target, _)| edges.contains_key(*target))
+                .map(|(target, data)| (*target, data.clone()))
+                .collect();
+
+            let node = Node {
+                label: node.label.clone(),
+                edges,
+                data,
+            };
+            nodes.insert(*key, node);
+        }
+        Graph {
+            nodes,
+            ..Default::default()
+        }
+    }
+
+    // Induced subgraphs of the ancestors and of the descendants, each including the node
+    pub fn partition_around(&self, label: &T) -> Option<(Graph<T, E>, Graph<T, E>)> {
+        self.get(label)?;
+        let key = hash(label);
+
+        let mut ancestors = self.ancestor_keys(key);
+        ancestors.insert(key);
+        let mut descendants = self.descendant_keys(key);
+        descendants.insert(key);
+        Some((self.induced(&ancestors), self.induced(&descendants)))
+    }
+}
+
+impl<T: Hash + Eq + Clone, E> Graph<T, E> {
+    // Every node with at most one incoming edge, kept from whichever predecessor first
+    // reaches it in a bredth first walk from the roots. Nodes only reachable through a
+    // cycle start a tree of their own.
+    pub fn spanning_forest(&self) -> Graph<T> {
+        let mut forest = Graph::new();
+        for node in self.nodes.values() {
+            forest.add(node.label.clone());
+        }
+
+        let in_degrees = self.in_degrees();
+        let roots = in_degrees.iter().filter(|(_, d)| **d == 0).map(|(k, _)| k);
+        let mut visited = HashSet::new();
+        for start in roots.chain(self.nodes.keys()) {
+            if !visited.insert(*start) {
+                continue;
+            }
+            let mut queue = VecDeque::new();
+            queue.push_back(*start);
+            while let Some(key) = queue.pop_front() {
+                for (target, weight) in &self.nodes[&key].edges {
+                    if visited.insert(*target) {
+                        forest
+                            .nodes
+                            .get_mut(&key)
+                            .unwrap()
+                            .edges
+                            .insert(*target, *weight);
+                        queue.push_back(*target);
+                    }
+                }
+            }
+        }
+        forest
+    }
+}
+
+impl<T, E> Graph<T, E> {
+    // Labels mapping to the same value become one node with the union of their edges,
+    // dropping any self loop or edge which would close a cycle. Edges keep their weight,
+    // taking the largest when several land on the same pair.
+    pub fn map_merging<U: Hash + Eq, F: FnMut(&T) -> U>(&self, mut f: F) -> Graph<U> {
+        let mut graph = Graph::new();
+        let mut mapped = HashMap::new();
+        for (key, node) in &self.nodes {
+            let label = f(&node.label);
+            let new_key = hash(&label);
+            if !graph.nodes.contains_key(&new_key) {
+                graph.add(label);
+            }
+            mapped.insert(*key, new_key);
+        }
+
+        for (key, node) in &self.nodes {
+            let from = mapped[key];
+            for (target, weight) in &node.edges {
+                let to = mapped[target];
+                if from == to {
+                    continue;
+                }
+                if let Some(existing) = graph.nodes.get_mut(&from).unwrap().edges.get_mut(&to) {
+                    *existing = (*existing).max(*weight);
+                } else if graph.path_keys(to, from, None).is_none() {
+                    graph
+                        .nodes
+                        .get_mut(&from)
+                        .unwrap()
+                        .edges
+                        .insert(to, *weight);
+                }
+            }
+        }
+        graph
+    }
+
+    // Merges each group of equivalent nodes into whichever was found first, keeping the
+    // union of their edges. Only edges moved by the merge are checked, dropping any which
+    // become a self loop or would close a cycle. Edges which land on the same pair keep the
+    // larger weight and the first data found.
+    pub fn merge_duplicates_by<F: Fn(&T, &T) -> bool>(&mut self, same: F) {
+        let mut representatives: Vec<u64> = Vec::new();
+        let mut merged = HashMap::new();
+        for (key, node) in &self.nodes {
+            let found = representatives
+                .iter()
+                .find(|r| same(&self.nodes[*r].label, &node.label));
+            match found {
+                Some(representative) => merged.insert(*key, *representative),
+                None => {
+                    representatives.push(*key);
+                    merged.insert(*key, *key)
+                }
+            };
+        }
+
+        let before = self.bulk_checkpoint();
+        let moves = |key: &u64| merged[key] != *key;
+        let mut edges = Vec::new();
+        for (key, node) in self.nodes.iter_mut() {
+            let targets = node
+                .edges
+                .keys()
+                .filter(|target| moves(key) || moves(target))
+                .copied()
+                .collect::<Vec<_>>();
+            for target in targets {
+                let weight = unmap(&mut node.edges, &target).unwrap();
+                edges.push((*key, target, weight, unmap(&mut node.data, &target)));
+            }
+        }
+        for (key, representative) in &merged {
+            if key != representative {
+                unmap(&mut self.nodes, key);
+            }
+        }
+
+        for (from, to, weight, data) in edges {
+            let (from, to) = (merged[&from], merged[&to]);
+            if from == to {
+                continue;
+            }
+            if let Some(existing) = self.nodes.get_mut(&from).unwrap().edges.get_mut(&to) {
+                *existing = (*existing).max(weight);
+            } else if self.path_keys(to, from, None).is_some() {
+                continue;
+            } else {
+                self.nodes.get_mut(&from).unwrap().edges.insert(to, weight);
+            }
+
+            let node = self.nodes.get_mut(&from).unwrap();
+            if let Some(data) = data {
+                node.data.entry(to).or_insert(data);
+            }
+        }
+        self.record_bulk(before);
+        self.edited_in_bulk();
+    }
+}
+
+impl<T: Hash + Eq> Graph<T> {
+    // Grows a graph from the seeds until no new nodes are discovered,
+    // skipping any edge which would close a cycle
+    pub fn explore<I, F>(seeds: I, mut successors: F) -> Graph<T>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(&T) -> Vec<T>,
+    {
+        let mut graph = Graph::new();
+        let mut queue = VecDeque::new();
+        for seed in seeds {
+            let key = hash(&seed);
+            if !graph.nodes.contains_key(&key) {
+                graph.add(seed);
+                queue.push_back(key);
+            }
+        }
+
+        while let Some(key) = queue.pop_front() {
+            for child in successors(&graph.nodes[&key].label) {
+                let target = hash(&child);
+                if !graph.nodes.contains_key(&target) {
+                    graph.add(child);
+                    queue.push_back(target);
+                }
+                if key != target && graph.path_keys(target, key, None).is_none() {
+                    graph.nodes.get_mut(&key).unwrap().edges.insert(target, 1);
+                }
+            }
+        }
+        graph
+    }
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    // Merges to into from, which takes over its edges in both directions, dropping any self
+    // loop or edge which would close a cycle. Edges already on a cycle through to are kept.
+    pub fn contract_edge(&mut self, from: &T, to: &T) -> bool {
+        if !self.is_connected(from, to) || from == to {
+            return false;
+        }
+
+        self.contract_keys(hash(from), hash(to));
+        true
+    }
+
+    // Removes, as remove would, every node not reachable from one of the roots.
+    // Roots missing from the graph are ignored.
+    pub fn retain_reachable_from<'a, I: IntoIterator<Item = &'a T>>(&mut self, roots: I)
+    where
+        T: 'a,
+    {
+        let mut live = HashSet::new();
+        let mut stack = Vec::new();
+        for root in roots {
+            let key = hash(root);
+            if self.nodes.contains_key(&key) && live.insert(key) {
+                stack.push(key);
+            }
+        }
+        while let Some(key) = stack.pop() {
+            for target in self.nodes[&key].edges.keys() {
+                if live.insert(*target) {
+                    stack.push(*target);
+                }
+            }
+        }
+
+        self.retain_keys(|key| live.contains(key));
+    }
+
+    // Merges each pass through node, one with a single predecessor and a single successor,
+    // into its predecessor until none are left. A node whose successor is also its
+    // predecessor is kept, so a cycle shrinks to two nodes rather than a self loop.
+    // Returns how many nodes were merged away.
+    pub fn collapse_chains(&mut self) -> usize {
+        let before = self.bulk_checkpoint();
+        let mut predecessors = self.predecessor_keys();
+        let mut pending = self.nodes.keys().copied().collect::<Vec<_>>();
+        let mut collapsed = 0;
+        while let Some(b) = pending.pop() {
+            let node = match self.nodes.get(&b) {
+                Some(node) if node.edges.len() == 1 && predecessors[&b].len() == 1 => node,
+                _ => continue,
+            };
+            let a = *predecessors[&b].iter().next().unwrap();
+            let c = *node.edges.keys().next().unwrap();
+            if a == b || c == b || c == a {
+                continue;
+            }
+
+            // Anything reaching c already went through a, so a -> c can't close a new cycle
+            let mut removed = unmap(&mut self.nodes, &b).unwrap();
+            predecessors.remove(&b);
+            let weight = removed.edges[&c];
+            let data = unmap(&mut removed.data, &c);
+            let node = self.nodes.get_mut(&a).unwrap();
+            unmap(&mut node.edges, &b);
+            unmap(&mut node.data, &b);
+            let sources = predecessors.get_mut(&c).unwrap();
+            sources.remove(&b);
+            let new = !node.edges.contains_key(&c);
+            if new {
+                node.edges.insert(c, weight);
+                if let Some(data) = data {
+                    node.data.insert(c, data);
+                }
+                sources.insert(a);
+            }
+
+            pending.push(a);
+            pending.push(c);
+            collapsed += 1;
+        }
+
+        if collapsed > 0 {
+            self.record_bulk(before);
+            self.edited_in_bulk();
+        }
+        collapsed
+    }
+
+    fn contract_keys(&mut self, a: u64, b: u64) {
+        // Cycles through b existed before contracting, so only the others need breaking
+        let (descendants, ancestors) = (self.descendant_keys(b), self.ancestor_keys(b));
+        let before = self.bulk_checkpoint();
+        let mut removed = unmap(&mut self.nodes, &b).unwrap();
+        let mut incoming = Vec::new();
+        for (key, node) in self.nodes.iter_mut() {
+            if let Some(weight) = unmap(&mut node.edges, &b) {
+                incoming.push((*key, weight, unmap(&mut node.data, &b)));
+            }
+        }
+
+        for (target, weight) in mem::take(&mut removed.edges) {
+            let data = unmap(&mut removed.data, &target);
+            // A self loop on the removed node would become a loop on a, so it's dropped
+            let cyclic = ancestors.contains(&target);
+            let target = if target == b { a } else { target };
+            self.contract_into(a, target, weight, data, cyclic);
+        }
+        for (key, weight, data) in incoming {
+            if key != a {
+                self.contract_into(key, a, weight, data, descendants.contains(&key));
+            }
+        }
+        self.record_bulk(before);
+        self.edited_in_bulk();
+    }
+
+    fn contract_into(&mut self, from: u64, to: u64, weight: i64, data: Option<E>, cyclic: bool) {
+        if from == to
+            || self.nodes[&from].edges.contains_key(&to)
+            || (!cyclic && self.path_keys(to, from, None).is_some())
+        {
+            return;
+        }
+
+        let node = self.nodes.get_mut(&from).unwrap();
+        node.edges.insert(to, weight);
+        if let Some(data) = data {
+            node.data.insert(to, data);
+        }
+    }
+}
+
+impl<T, E> Graph<T, E> {
+    // Same structure once this graph's labels are mapped, where map should be a bijection
+    pub fn is_isomorphic_by<U, D, F>(&self, other: &Graph<U, D>, map: F) -> bool
+    where
+        U: Hash + Eq,
+        F: Fn(&T) -> U,
+    {
+        if self.nodes.len() != other.nodes.len() {
+            return false;
+        }
+
+        let mapped: HashMap<u64, u64> = self
+            .nodes
+            .iter()
+            .map(|(key, node)| (*key, hash(&map(&node.label))))
+            .collect();
+        let targets = mapped.values().collect::<HashSet<_>>();
+        if targets.len() != self.nodes.len() {
+            return false;
+        }
+
+        self.nodes
+            .iter()
+            .all(|(key, node)| match other.nodes.get(&mapped[key]) {
+                Some(other_node) => {
+                    other_node.edges.len() == node.edges.len()
+                        && node
+                            .edges
+                            .keys()
+                            .all(|target| other_node.edges.contains_key(&mapped[target]))
+                }
+                None => false,
+            })
+    }
+
+    // Same structure once equivalent nodes are merged: the classes of nodes under same
+    // which appear in each graph, and the class to class edges, must match. How many
+    // nodes a class holds doesn't matter. Only meaningful when same is a genuine
+    // equivalence relation.
+    pub fn equal_under<D, F: Fn(&T, &T) -> bool>(&self, other: &Graph<T, D>, same: F) -> bool {
+        let mut representatives: Vec<&T> = Vec::new();
+        let mut class_of = |label| match representatives.iter().position(|r| same(r, label)) {
+            Some(class) => class,
+            None => {
+                representatives.push(label);
+                representatives.len() - 1
+            }
+        };
+
+        let classes = self
+            .nodes
+            .iter()
+            .map(|(key, node)| (*key, class_of(&node.label)))
+            .collect::<HashMap<_, _>>();
+        let other_classes = other
+            .nodes
+            .iter()
+            .map(|(key, node)| (*key, class_of(&node.label)))
+            .collect::<HashMap<_, _>>();
+
+        let present = classes.values().collect::<HashSet<_>>();
+        let other_present = other_classes.values().collect::<HashSet<_>>();
+        let edges = self.edges_between_classes(&classes).collect::<HashSet<_>>();
+        let other_edges = other
+            .edges_between_classes(&other_classes)
+            .collect::<HashSet<_>>();
+        present == other_present && edges == other_edges
+    }
+
+    fn edges_between_classes<'a>(
+        &'a self,
+        classes: &'a HashMap<u64, usize>,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.nodes.iter().flat_map(move |(key, node)| {
+            node.edges
+                .keys()
+                .map(move |target| (classes[key], classes[target]))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersection() {
+        let mut g = Graph::init('a'..='d');
+        let mut h = Graph::init('b'..='e');
+
+        // a -> b -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        // b -> c    d -> e
+        // b -> d
+        assert!(h.connect(&'b', &'c'));
+        assert!(h.connect(&'b', &'d'));
+        assert!(h.connect(&'d', &'e'));
+
+        let i = g.intersection(&h);
+        assert!(i.connections(&'a').is_none());
+        assert!(i.connections(&'e').is_none());
+        assert!(i.is_connected(&'b', &'c'));
+        assert!(!i.is_connected(&'b', &'d'));
+        assert!(!i.is_connected(&'c', &'d'));
+        assert_eq!(i.edges().count(), 1);
+    }
+
+    #[test]
+    fn map_merging() {
+        let mut g = Graph::init(vec!["a1", "a2", "b1", "c1"]);
+
+        // a1 -> a2 -> b1 -> c1
+        //       a2 -------> c1
+        assert!(g.connect(&"a1", &"a2"));
+        assert!(g.connect(&"a2", &"b1"));
+        assert!(g.connect(&"b1", &"c1"));
+        assert!(g.connect(&"a2", &"c1"));
+
+        // a -> b -> c
+        // a ------> c
+        let m = g.map_merging(|label| label.chars().next().unwrap());
+        assert_eq!(m.nodes.len(), 3);
+        assert!(!m.is_connected(&'a', &'a'));
+        assert!(m.is_connected(&'a', &'b'));
+        assert!(m.is_connected(&'b', &'c'));
+        assert!(m.is_connected(&'a', &'c'));
+
+        // Merging everything into one node leaves only self loops, which are dropped
+        let m = g.map_merging(|_| 'x');
+        assert!(m.connections(&'x').unwrap().is_empty());
+
+        // a1 -> b1    b2 -> a2 merges to a <-> b, so one direction is dropped
+        let mut h = Graph::init(vec!["a1", "a2", "b1", "b2"]);
+        assert!(h.connect(&"a1", &"b1"));
+        assert!(h.connect(&"b2", &"a2"));
+
+        let m = h.map_merging(|label| label.chars().next().unwrap());
+        assert!(m.is_connected(&'a', &'b') != m.is_connected(&'b', &'a'));
+
+        // a1 -> b1 (2)    a2 -> b2 (5)    b1 -> c1 (3)
+        let mut h = Graph::init(vec!["a1", "a2", "b1", "b2", "c1"]);
+        assert!(h.set_edge_weight(&"a1", &"b1", 2));
+        assert!(h.set_edge_weight(&"a2", &"b2", 5));
+        assert!(h.set_edge_weight(&"b1", &"c1", 3));
+
+        let m = h.map_merging(|label| label.chars().next().unwrap());
+        assert_eq!(m.edge_weight(&'a', &'b'), Some(5));
+        assert_eq!(m.edge_weight(&'b', &'c'), Some(3));
+    }
+
+    #[test]
+    fn merge_duplicates_by() {
+        let mut g = Graph::init(vec!["a1", "a2", "b1", "c1"]);
+
+        // a1 -> b1    c1 -> a2
+        assert!(g.connect(&"a1", &"b1"));
+        assert!(g.connect(&"c1", &"a2"));
+
+        g.merge_duplicates_by(|x, y| x[..1] == y[..1]);
+        assert_eq!(g.nodes.len(), 3);
+
+        let a = if g.node_view(&"a1").is_some() {
+            "a1"
+        } else {
+            "a2"
+        };
+        assert!(g.is_connected(&a, &"b1"));
+        assert!(g.is_connected(&"c1", &a));
+    }
+
+    #[test]
+    fn merge_duplicates_by_keeps_unmerged_edges() {
+        let mut g = Graph::init('a'..='b');
+
+        // a <-> b, a -> a
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'a'));
+        assert!(g.connect(&'a', &'a'));
+
+        g.merge_duplicates_by(|x, y| x == y);
+        assert_eq!(g.edges().count(), 3);
+
+        // a1 -> b, a2 -> b with different weights merge to the heavier edge
+        let mut g = Graph::init(vec!["a1", "a2", "b"]);
+        assert!(g.set_edge_weight(&"a1", &"b", 2));
+        assert!(g.set_edge_weight(&"a2", &"b", 5));
+
+        g.merge_duplicates_by(|x, y| x[..1] == y[..1]);
+        let a = if g.node_view(&"a1").is_some() {
+            "a1"
+        } else {
+            "a2"
+        };
+        assert_eq!(g.edge_weight(&a, &"b"), Some(5));
+        assert_eq!(g.edges().count(), 1);
+    }
+
+    #[test]
+    fn partition_around() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c -> d
+        //      e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        let (before, after) = g.partition_around(&'b').unwrap();
+        assert!(before.is_connected(&'a', &'b'));
+        assert_eq!(before.nodes.len(), 2);
+        assert!(after.is_connected(&'b', &'c'));
+        assert!(after.is_connected(&'c', &'d'));
+        assert_eq!(after.nodes.len(), 3);
+        assert!(after.connections(&'e').is_none());
+
+        assert!(g.partition_around(&'f').is_none());
+    }
+
+    #[test]
+    fn explore() {
+        // n -> n + 1 and n -> 2n up to 8, with 8 -> 1 closing a cycle
+        let g = Graph::explore(vec![1], |n| match n {
+            8 => vec![1],
+            n if n * 2 <= 8 => vec![n + 1, n * 2],
+            n => vec![n + 1],
+        });
+
+        assert_eq!(g.nodes.len(), 8);
+        assert!(g.is_connected(&1, &2));
+        assert!(g.is_connected(&3, &6));
+        assert!(!g.is_connected(&8, &1));
+        assert!(g.topological_sort().is_some());
+    }
+
+    #[test]
+    fn contract_edge() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c
+        // d -> b -> e
+        // a ------> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'d', &'b'));
+        assert!(g.connect(&'b', &'e'));
+        assert!(g.connect(&'a', &'e'));
+
+        assert!(!g.contract_edge(&'a', &'c'));
+        assert!(g.contract_edge(&'a', &'b'));
+        assert!(g.connections(&'b').is_none());
+        assert!(g.is_connected(&'a', &'c'));
+        assert!(g.is_connected(&'a', &'e'));
+        assert!(g.is_connected(&'d', &'a'));
+        assert!(!g.is_connected(&'a', &'a'));
+        assert_eq!(g.edges().count(), 3);
+    }
+
+    #[test]
+    fn contract_edge_cycle() {
+        let mut g = Graph::init('a'..='c');
+
+        // a -> b -> c -> a
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'a'));
+
+        assert!(g.contract_edge(&'a', &'b'));
+        assert!(g.is_connected(&'a', &'c'));
+        assert!(g.is_connected(&'c', &'a'));
+        assert_eq!(g.edges().count(), 2);
+
+        // a -> b, a -> c -> b has no cycle until b merges into a, so c -> a is dropped
+        let mut g = Graph::init('a'..='c');
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'c', &'b'));
+
+        assert!(g.contract_edge(&'a', &'b'));
+        assert!(g.is_connected(&'a', &'c'));
+        assert!(!g.is_connected(&'c', &'a'));
+    }
+
+    #[test]
+    fn contract_edge_self_loop() {
+        let mut g = Graph::init('a'..='c');
+
+        // a -> b -> c, b -> b
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'b', &'b'));
+
+        assert!(g.contract_edge(&'a', &'b'));
+        assert_eq!(
+            g.connections(&'a').unwrap(),
+            [&'c'].iter().cloned().collect()
+        );
+        assert_eq!(g.edges().count(), 1);
+        assert!(g.validate().is_empty());
+    }
+
+    #[test]
+    fn is_isomorphic_by() {
+        let mut g = Graph::init('a'..='c');
+        let mut h = Graph::init(1..=3);
+
+        // a -> b -> c    1 -> 2 -> 3
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(h.connect(&1, &2));
+        assert!(h.connect(&2, &3));
+
+        let index = |c: &char| *c as u32 - 'a' as u32 + 1;
+        assert!(g.is_isomorphic_by(&h, index));
+        assert!(!g.is_isomorphic_by(&h, |c| 4 - index(c)));
+        assert!(!g.is_isomorphic_by(&h, |_| 1));
+
+        assert!(h.connect(&1, &3));
+        assert!(!g.is_isomorphic_by(&h, index));
+    }
+
+    #[test]
+    fn collapse_chains() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> c -> d -> e
+        // a -------------> d
+        //                  f -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.connect(&'f', &'d'));
+        assert!(g.set_edge_weight(&'c', &'d', 3));
+
+        assert_eq!(g.collapse_chains(), 2);
+        assert!(g.get(&'b').is_none());
+        assert!(g.get(&'c').is_none());
+        assert!(g.get(&'d').is_some());
+        assert!(g.is_connected(&'a', &'d'));
+        assert_eq!(g.edge_weight(&'a', &'d'), Some(1));
+        assert_eq!(g.edges().count(), 3);
+        assert_eq!(g.collapse_chains(), 0);
+    }
+
+    #[test]
+    fn collapse_chains_cycle() {
+        let mut g = Graph::init('a'..='c');
+
+        // a -> b -> c -> a
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'a'));
+
+        assert_eq!(g.collapse_chains(), 1);
+        assert_eq!(g.nodes.len(), 2);
+        assert_eq!(g.edges().count(), 2);
+        for edge in g.edges() {
+            assert!(g.is_connected(edge.to, edge.from));
+        }
+    }
+
+    #[test]
+    fn collapse_chains_long() {
+        let mut g = Graph::init(0..5000);
+        for i in 0..4999 {
+            assert!(g.connect(&i, &(i + 1)));
+        }
+
+        assert_eq!(g.collapse_chains(), 4998);
+        assert!(g.is_connected(&0, &4999));
+        assert_eq!(g.edges().count(), 1);
+    }
+
+    #[test]
+    fn equal_under() {
+        let base = |label: &&str| label.split('@').next().unwrap().to_string();
+        let same = |a: &&str, b: &&str| base(a) == base(b);
+
+        let mut g = Graph::init(vec!["app@1", "lib@1", "log@2"]);
+        assert!(g.connect(&"app@1", &"lib@1"));
+        assert!(g.connect(&"lib@1", &"log@2"));
+
+        let mut h = Graph::init(vec!["app@2", "lib@3", "log@2"]);
+        assert!(h.connect(&"app@2", &"lib@3"));
+        assert!(h.connect(&"lib@3", &"log@2"));
+        assert!(g.equal_under(&h, same));
+        assert!(!g.equal_under(&h, |a, b| a == b));
+
+        assert!(h.connect(&"app@2", &"log@2"));
+        assert!(!g.equal_under(&h, same));
+
+        let k = Graph::init(vec!["app@1", "app@2", "log@2"]);
+        assert!(k.equal_under(&k, same));
+
+        // Two equivalent nodes with the same edges merge into one
+        let mut m = Graph::init(vec!["app@1", "app@2", "lib@1", "log@1"]);
+        assert!(m.connect(&"app@1", &"lib@1"));
+        assert!(m.connect(&"app@2", &"lib@1"));
+        assert!(m.connect(&"lib@1", &"log@1"));
+        assert!(g.equal_under(&m, same));
+        assert!(!g.equal_under(&k, same));
+    }
+
+    #[test]
+    fn spanning_forest() {
+        let mut g = Graph::init('a'..='g');
+
+        // a -> b -> d
+        // a -> c -> d -> e
+        // f <-> g
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.biconnect(&'f', &'g'));
+
+        let forest = g.spanning_forest();
+        assert_eq!(forest.nodes.len(), 7);
+        assert_eq!(forest.edges().count(), 5);
+        assert!(forest.in_degrees().values().all(|d| *d <= 1));
+        assert!(forest.is_connected(&'a', &'b'));
+        assert!(forest.is_connected(&'a', &'c'));
+        assert!(forest.is_connected(&'b', &'d') != forest.is_connected(&'c', &'d'));
+        assert!(forest.is_forest());
+    }
+
+    #[test]
+    fn retain_reachable_from() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> c
+        // d -> b
+        // d -> e -> f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'d', &'b'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.connect(&'e', &'f'));
+
+        g.retain_reachable_from(&['a', 'f', 'z']);
+        let mut labels = g.clone().into_labels().collect::<Vec<_>>();
+        labels.sort();
+        assert_eq!(labels, vec!['a', 'b', 'c', 'f']);
+        assert_eq!(g.edges().count(), 2);
+        assert!(g.is_connected(&'a', &'b'));
+        assert!(g.is_connected(&'b', &'c'));
+
+        g.retain_reachable_from(&[]);
+        assert_eq!(g.into_labels().count(), 0);
+
+        let mut g = Graph::init('a'..='c').with_history();
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'c', &'b'));
+        g.retain_reachable_from(&['a']);
+        assert!(g.connections(&'a').unwrap().contains(&'b'));
+        assert!(g.get(&'c').is_none());
+        assert_eq!(g.history().last(), Some(&Op::Remove('c')));
+    }
+
+    #[test]
+    fn retain_reachable_from_long() {
+        let mut g = Graph::init(0..5000);
+        for i in 0..4999 {
+            assert!(g.connect(&i, &(i + 1)));
+        }
+
+        g.retain_reachable_from(&[4998]);
+        assert_eq!(g.nodes.len(), 2);
+        assert!(g.is_connected(&4998, &4999));
+    }
+}
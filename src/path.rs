@@ -0,0 +1,530 @@
+use crate::{graph::*, hash};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+impl<T, E> Graph<T, E> {
+    // Shortest path of keys by bredth first search, optionally ignoring one edge
+    pub(crate) fn path_keys(
+        &self,
+        from: u64,
+        to: u64,
+        skip: Option<(u64, u64)>,
+    ) -> Option<Vec<u64>> {
+        if !self.nodes.contains_key(&from) || !self.nodes.contains_key(&to) {
+            return None;
+        }
+
+        let mut parents = HashMap::new();
+        parents.insert(from, from);
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+
+        while let Some(key) = queue.pop_front() {
+            if key == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while current != from {
+                    current = parents[&current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for target in self.nodes[&key].edges.keys() {
+                if skip == Some((key, *target)) || parents.contains_key(target) {
+                    continue;
+                }
+                parents.insert(*target, key);
+                queue.push_back(*target);
+            }
+        }
+        None
+    }
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    // An existing edge whose removal would let from -> to be added without a cycle
+    pub fn suggest_edge_to_break(&self, from: &T, to: &T) -> Option<(&T, &T)> {
+        let a = hash(from);
+        let b = hash(to);
+        let path = self.path_keys(b, a, None)?;
+
+        let (x, y) = path
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|edge| self.path_keys(b, a, Some(*edge)).is_none())?;
+        Some((&self.nodes[&x].label, &self.nodes[&y].label))
+    }
+
+    // Edge count of the longest path, None if unreachable or a cycle is reachable from
+    pub fn longest_path_len(&self, from: &T, to: &T) -> Option<usize> {
+        let a = hash(from);
+        let b = hash(to);
+        self.get(from)?;
+
+        let mut region = self.descendant_keys(a);
+        if a == b && !region.contains(&a) {
+            return Some(0);
+        }
+        if !region.contains(&b) {
+            return None;
+        }
+        region.insert(a);
+
+        let order = self.topological_keys_within(&region)?;
+        let mut lengths = HashMap::new();
+        lengths.insert(a, 0);
+        for key in order {
+            let length = lengths[&key];
+            for target in self.nodes[&key].edges.keys() {
+                let target_length = lengths.entry(*target).or_insert(0);
+                *target_length = (*target_length).max(length + 1);
+            }
+        }
+        lengths.get(&b).copied()
+    }
+
+    pub fn shortest_path(&self, from: &T, to: &T) -> Option<Vec<&T>> {
+        let path = self.path_keys(hash(from), hash(to), None)?;
+        Some(path.iter().map(|key| &self.nodes[key].label).collect())
+    }
+
+    // Length is the number of edges, so a path from a node to itself is 0
+    pub fn shortest_path_with_len(&self, from: &T, to: &T) -> Option<(Vec<&T>, usize)> {
+        let path = self.shortest_path(from, to)?;
+        let len = path.len() - 1;
+        Some((path, len))
+    }
+
+    // Nodes along a maximum length path, None if the graph contains a cycle
+    pub fn longest_path(&self) -> Option<Vec<&T>> {
+        let order = self.topological_keys()?;
+        let mut best: HashMap<u64, (usize, Option<u64>)> = HashMap::new();
+        for key in &order {
+            let (length, _) = *best.entry(*key).or_insert((0, None));
+            for target in self.nodes[key].edges.keys() {
+                let entry = best.entry(*target).or_insert((0, None));
+                if entry.0 < length + 1 {
+                    *entry = (length + 1, Some(*key));
+                }
+            }
+        }
+
+        let mut current = best
+            .iter()
+            .max_by_key(|(_, (length, _))| *length)
+            .map(|(k, _)| *k);
+        let mut path = Vec::new();
+        while let Some(key) = current {
+            path.push(&self.nodes[&key].label);
+            current = best[&key].1;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn critical_path_edges(&self) -> Option<Vec<(&T, &T)>> {
+        let path = self.longest_path()?;
+        Some(path.windows(2).map(|pair| (pair[0], pair[1])).collect())
+    }
+
+    pub fn has_path(&self, from: &T, to: &T) -> bool {
+        let (a, b) = (hash(from), hash(to));
+        if self.query_cache.is_none() {
+            return self.path_keys(a, b, None).is_some();
+        }
+        self.nodes.contains_key(&a)
+            && self.nodes.contains_key(&b)
+            && (a == b || self.cached_descendant_keys(a).contains(&b))
+    }
+
+    // Every root to leaf path, None if the graph contains a cycle.
+    // The number of chains can grow exponentially with the number of nodes.
+    pub fn maximal_chains(&self) -> Option<Vec<Vec<&T>>> {
+        self.topological_keys()?;
+
+        let mut chains = Vec::new();
+        for (key, degree) in self.in_degrees() {
+            if degree == 0 {
+                self.extend_chain(key, &mut chains);
+            }
+        }
+
+        let chains = chains
+            .into_iter()
+            .map(|chain| chain.iter().map(|k| &self.nodes[k].label).collect())
+            .collect();
+        Some(chains)
+    }
+
+    // Every chain from root to a leaf, the stack holding the chain so far
+    fn extend_chain(&self, root: u64, chains: &mut Vec<Vec<u64>>) {
+        if self.nodes[&root].edges.is_empty() {
+            chains.push(vec![root]);
+        }
+        let mut stack = vec![(root, self.nodes[&root].edges.keys())];
+        while let Some((_, targets)) = stack.last_mut() {
+            match targets.next() {
+                Some(target) => {
+                    let next = &self.nodes[target].edges;
+                    stack.push((*target, next.keys()));
+                    if next.is_empty() {
+                        chains.push(stack.iter().map(|(key, _)| *key).collect());
+                    }
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    // Earliest and latest start of each node given node durations, where nodes with equal
+    // starts lie on the critical path. None if the graph contains a cycle.
+    pub fn critical_path_method<F: Fn(&T) -> u64>(
+        &self,
+        duration: F,
+    ) -> Option<HashMap<&T, (u64, u64)>> {
+        let order = self.topological_keys()?;
+        let durations: HashMap<u64, u64> = order
+            .iter()
+            .map(|k| (*k, duration(&self.nodes[k].label)))
+            .collect();
+
+        let mut earliest: HashMap<u64, u64> = order.iter().map(|k| (*k, 0)).collect();
+        for key in &order {
+            let finish = earliest[key] + durations[key];
+            for target in self.nodes[key].edges.keys() {
+                let start = earliest.get_mut(target).unwrap();
+                *start = (*start).max(finish);
+            }
+        }
+
+        let end = order
+            .iter()
+            .map(|k| earliest[k] + durations[k])
+            .max()
+            .unwrap_or(0);
+        let mut latest = HashMap::new();
+        for key in order.iter().rev() {
+            let finish = self.nodes[key]
+                .edges
+                .keys()
+                .map(|target| latest[target])
+                .min()
+                .unwrap_or(end);
+            latest.insert(*key, finish - durations[key]);
+        }
+
+        let starts = order
+            .iter()
+            .map(|k| (&self.nodes[k].label, (earliest[k], latest[k])))
+            .collect();
+        Some(starts)
+    }
+
+    // Edges whose target is still reachable from their source without them. A self loop is
+    // never redundant as the empty path back to itself isn't a longer one.
+    pub fn redundant_edges(&self) -> Vec<(&T, &T)> {
+        let mut redundant = Vec::new();
+        for (key, node) in &self.nodes {
+            for target in node.edges.keys() {
+                if key != target
+                    && self
+                        .path_keys(*key, *target, Some((*key, *target)))
+                        .is_some()
+                {
+                    redundant.push((&node.label, &self.nodes[target].label));
+                }
+            }
+        }
+        redundant
+    }
+
+    // For each edge on some from -> to path, how many distinct such paths use it. Counts
+    // can grow exponentially so they saturate at usize::MAX rather than overflow.
+    // None if either node is missing or a cycle lies on a path between them.
+    pub fn edge_path_counts(&self, from: &T, to: &T) -> Option<HashMap<(&T, &T), usize>> {
+        let a = hash(from);
+        let b = hash(to);
+        self.get(from)?;
+        self.get(to)?;
+
+        let mut descendants = self.descendant_keys(a);
+        if a != b && !descendants.contains(&b) {
+            return Some(HashMap::new());
+        }
+        descendants.insert(a);
+        let mut ancestors = self.ancestor_keys(b);
+        ancestors.insert(b);
+        let region = descendants
+            .intersection(&ancestors)
+            .copied()
+            .collect::<HashSet<_>>();
+        let order = self.topological_keys_within(&region)?;
+
+        let mut into: HashMap<u64, usize> = HashMap::new();
+        into.insert(a, 1);
+        for key in &order {
+            let paths = into.get(key).copied().unwrap_or(0);
+            for target in self.nodes[key].edges.keys().filter(|t| region.contains(t)) {
+                let count = into.entry(*target).or_insert(0);
+                *count = count.saturating_add(paths);
+            }
+        }
+        let mut out_of: HashMap<u64, usize> = HashMap::new();
+        out_of.insert(b, 1);
+        for key in order.iter().rev() {
+            let paths = self.nodes[key]
+                .edges
+                .keys()
+                .filter(|t| region.contains(t))
+                .fold(0, |sum: usize, t| sum.saturating_add(out_of[t]));
+            let count = out_of.entry(*key).or_insert(0);
+            *count = count.saturating_add(paths);
+        }
+
+        let mut counts = HashMap::new();
+        for key in &order {
+            let node = &self.nodes[key];
+            for target in node.edges.keys().filter(|t| region.contains(t)) {
+                let label = &self.nodes[target].label;
+                counts.insert(
+                    (&node.label, label),
+                    into[key].saturating_mul(out_of[target]),
+                );
+            }
+        }
+        Some(counts)
+    }
+
+    // Whether the edge exists and is the only way from reaches to, so disconnecting it
+    // would leave to unreachable from from
+    pub fn is_bridge(&self, from: &T, to: &T) -> bool {
+        let (a, b) = (hash(from), hash(to));
+        self.is_connected(from, to) && self.path_keys(a, b, Some((a, b))).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_edge_to_break() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c -> d
+        //      b -> e -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'b', &'e'));
+        assert!(g.connect(&'e', &'d'));
+
+        // No cycle so nothing to break
+        assert!(g.suggest_edge_to_break(&'a', &'d').is_none());
+
+        // d -> a would close a -> b -> ... -> d, only a -> b is on every path
+        assert_eq!(g.suggest_edge_to_break(&'d', &'a'), Some((&'a', &'b')));
+
+        // d -> b has two paths back so no single edge suffices
+        assert!(g.suggest_edge_to_break(&'d', &'b').is_none());
+    }
+
+    #[test]
+    fn longest_path_len() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c -> d
+        // a ------------> d
+        //      e -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'e', &'c'));
+
+        assert_eq!(g.longest_path_len(&'a', &'d'), Some(3));
+        assert_eq!(g.longest_path_len(&'e', &'d'), Some(2));
+        assert_eq!(g.longest_path_len(&'a', &'a'), Some(0));
+        assert_eq!(g.longest_path_len(&'d', &'a'), None);
+        assert_eq!(g.longest_path_len(&'a', &'e'), None);
+    }
+
+    #[test]
+    fn shortest_path() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        // a -------> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'c'));
+
+        assert_eq!(g.shortest_path(&'a', &'d'), Some(vec![&'a', &'c', &'d']));
+        assert_eq!(
+            g.shortest_path_with_len(&'a', &'d'),
+            Some((vec![&'a', &'c', &'d'], 2))
+        );
+        assert_eq!(g.shortest_path_with_len(&'b', &'b'), Some((vec![&'b'], 0)));
+        assert_eq!(g.shortest_path_with_len(&'d', &'a'), None);
+    }
+
+    #[test]
+    fn longest_path() {
+        let mut g = Graph::init('a'..='e');
+        assert!(Graph::<char>::new().longest_path().unwrap().is_empty());
+
+        // a -> b -> c -> d
+        // a ------------> d
+        //                 e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'d'));
+
+        assert_eq!(g.longest_path(), Some(vec![&'a', &'b', &'c', &'d']));
+        assert_eq!(
+            g.critical_path_edges(),
+            Some(vec![(&'a', &'b'), (&'b', &'c'), (&'c', &'d')])
+        );
+
+        assert!(g.connect(&'d', &'a'));
+        assert!(g.critical_path_edges().is_none());
+    }
+
+    #[test]
+    fn maximal_chains() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> d
+        // a -> c -> d
+        //           e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+
+        let mut chains = g.maximal_chains().unwrap();
+        chains.sort();
+        assert_eq!(
+            chains,
+            vec![vec![&'a', &'b', &'d'], vec![&'a', &'c', &'d'], vec![&'e']]
+        );
+    }
+
+    #[test]
+    fn maximal_chains_long_path() {
+        let mut g = Graph::init(0..200_000);
+        for i in 1..200_000 {
+            assert!(g.connect(&(i - 1), &i));
+        }
+
+        let chains = g.maximal_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].len(), 200_000);
+    }
+
+    #[test]
+    fn critical_path_method() {
+        let mut g = Graph::init('a'..='d');
+
+        // a(2) -> b(3) -> d(1)
+        // a(2) -> c(1) -> d(1)
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+
+        let starts = g
+            .critical_path_method(|label| match label {
+                'a' => 2,
+                'b' => 3,
+                _ => 1,
+            })
+            .unwrap();
+        assert_eq!(starts[&'a'], (0, 0));
+        assert_eq!(starts[&'b'], (2, 2));
+        assert_eq!(starts[&'c'], (2, 4));
+        assert_eq!(starts[&'d'], (5, 5));
+    }
+
+    #[test]
+    fn redundant_edges() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        // a -------> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'c'));
+
+        assert_eq!(g.redundant_edges(), vec![(&'a', &'c')]);
+
+        assert!(g.connect(&'d', &'d'));
+        assert_eq!(g.redundant_edges(), vec![(&'a', &'c')]);
+    }
+
+    #[test]
+    fn edge_path_counts() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> d -> e
+        // a -> c -> d
+        //           d -> f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.connect(&'d', &'f'));
+
+        let counts = g.edge_path_counts(&'a', &'e').unwrap();
+        assert_eq!(counts.len(), 5);
+        assert_eq!(counts[&(&'a', &'b')], 1);
+        assert_eq!(counts[&(&'b', &'d')], 1);
+        assert_eq!(counts[&(&'d', &'e')], 2);
+
+        assert!(g.edge_path_counts(&'e', &'a').unwrap().is_empty());
+        assert!(g.edge_path_counts(&'a', &'z').is_none());
+
+        assert!(g.connect(&'d', &'b'));
+        assert!(g.edge_path_counts(&'a', &'e').is_none());
+    }
+
+    #[test]
+    fn edge_path_counts_saturate() {
+        let mut g = Graph::init(0..=100);
+        for i in 0..=100 {
+            g.connect(&i, &(i + 1));
+            g.connect(&i, &(i + 2));
+        }
+
+        // Fibonacci many paths, far more than fit in a u64
+        let counts = g.edge_path_counts(&0, &100).unwrap();
+        assert_eq!(counts[&(&0, &1)], usize::MAX);
+        assert_eq!(counts[&(&99, &100)], usize::MAX);
+        assert_eq!(counts[&(&98, &100)], usize::MAX);
+    }
+
+    #[test]
+    fn is_bridge() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c
+        // a ------> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        assert!(!g.is_bridge(&'a', &'c'));
+        assert!(g.is_bridge(&'a', &'b'));
+        assert!(g.is_bridge(&'c', &'d'));
+        assert!(!g.is_bridge(&'a', &'d'));
+    }
+}
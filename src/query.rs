@@ -0,0 +1,798 @@
+use crate::{graph::*, hash};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Before, // a reaches b
+    After,  // b reaches a
+    Cycle,  // each reaches the other
+    Incomparable,
+    Missing,
+}
+
+// Every key reachable from start by following next, not counting start itself
+fn flood<F, I>(start: u64, next: F) -> HashSet<u64>
+where
+    F: Fn(u64) -> I,
+    I: IntoIterator<Item = u64>,
+{
+    let mut visited = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(key) = stack.pop() {
+        for other in next(key) {
+            if visited.insert(other) {
+                stack.push(other);
+            }
+        }
+    }
+    visited
+}
+
+impl<T, E> Graph<T, E> {
+    pub(crate) fn predecessor_keys(&self) -> HashMap<u64, HashSet<u64>> {
+        let mut predecessors: HashMap<u64, HashSet<u64>> =
+            self.nodes.keys().map(|k| (*k, HashSet::new())).collect();
+        for (key, node) in &self.nodes {
+            for target in node.edges.keys() {
+                predecessors.get_mut(target).unwrap().insert(*key);
+            }
+        }
+        predecessors
+    }
+
+    pub(crate) fn descendant_keys(&self, key: u64) -> HashSet<u64> {
+        flood(key, |k| {
+            self.nodes[&k].edges.keys().copied().collect::<Vec<_>>()
+        })
+    }
+
+    // Consults the query cache, if there is one, before walking
+    pub(crate) fn cached_descendant_keys(&self, key: u64) -> HashSet<u64> {
+        match &self.query_cache {
+            Some(cache) => cache.get_or_insert_with(key, || self.descendant_keys(key)),
+            None => self.descendant_keys(key),
+        }
+    }
+
+    pub(crate) fn ancestor_keys(&self, key: u64) -> HashSet<u64> {
+        let predecessors = self.predecessor_keys();
+        flood(key, |k| {
+            predecessors[&k].iter().copied().collect::<Vec<_>>()
+        })
+    }
+
+    // Acyclic with every node having at most one parent
+    pub fn is_forest(&self) -> bool {
+        self.in_degrees().values().all(|d| *d <= 1) && self.topological_keys().is_some()
+    }
+
+    pub fn is_tree(&self) -> bool {
+        let roots = self.in_degrees().values().filter(|d| **d == 0).count();
+        roots == 1 && self.is_forest()
+    }
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    pub(crate) fn labels<'a, I: IntoIterator<Item = &'a u64>>(&self, keys: I) -> HashSet<&T> {
+        keys.into_iter().map(|k| &self.nodes[k].label).collect()
+    }
+
+    pub fn descendants(&self, label: &T) -> Option<HashSet<&T>> {
+        let key = hash(label);
+        self.get(label)?;
+        Some(self.labels(&self.cached_descendant_keys(key)))
+    }
+
+    pub fn ancestors(&self, label: &T) -> Option<HashSet<&T>> {
+        let key = hash(label);
+        self.get(label)?;
+        Some(self.labels(&self.ancestor_keys(key)))
+    }
+
+    // Every node on some path from -> to, including both ends
+    pub fn nodes_between(&self, from: &T, to: &T) -> HashSet<&T> {
+        let a = hash(from);
+        let b = hash(to);
+        if self.get(from).is_none() || self.get(to).is_none() {
+            return HashSet::new();
+        }
+
+        let mut descendants = self.descendant_keys(a);
+        if a != b && !descendants.contains(&b) {
+            return HashSet::new();
+        }
+        descendants.insert(a);
+
+        let mut ancestors = self.ancestor_keys(b);
+        ancestors.insert(b);
+        self.labels(descendants.intersection(&ancestors))
+    }
+
+    // Common descendants which aren't descended from another common descendant
+    pub fn highest_common_descendants(&self, a: &T, b: &T) -> HashSet<&T> {
+        if self.get(a).is_none() || self.get(b).is_none() {
+            return HashSet::new();
+        }
+
+        let common = self
+            .descendant_keys(hash(a))
+            .intersection(&self.descendant_keys(hash(b)))
+            .copied()
+            .collect::<HashSet<_>>();
+
+        let mut highest = common.clone();
+        for key in &common {
+            for descendant in self.descendant_keys(*key) {
+                highest.remove(&descendant);
+            }
+        }
+        self.labels(&highest)
+    }
+
+    pub fn isolated_nodes(&self) -> HashSet<&T> {
+        let in_degrees = self.in_degrees();
+        let isolated = self
+            .nodes
+            .iter()
+            .filter(|(key, node)| in_degrees[*key] == 0 && node.edges.is_empty())
+            .map(|(key, _)| key);
+        self.labels(isolated)
+    }
+
+    // Weakly connected component, following edges in both directions
+    pub fn component_of(&self, label: &T) -> Option<HashSet<&T>> {
+        let key = hash(label);
+        self.get(label)?;
+
+        let predecessors = self.predecessor_keys();
+        let mut component = flood(key, |k| {
+            let targets = self.nodes[&k].edges.keys();
+            targets
+                .chain(&predecessors[&k])
+                .copied()
+                .collect::<Vec<_>>()
+        });
+        component.insert(key);
+        Some(self.labels(&component))
+    }
+
+    // Every path from root to node passes through dominator, false if node is unreachable
+    pub fn dominates(&self, root: &T, dominator: &T, node: &T) -> bool {
+        let (r, d, n) = (hash(root), hash(dominator), hash(node));
+        if self.get(root).is_none() || self.get(dominator).is_none() {
+            return false;
+        }
+        if n == r {
+            return d == r; // only the root dominates the root
+        }
+        if !self.descendant_keys(r).contains(&n) {
+            return false;
+        }
+        if d == r || d == n {
+            return true;
+        }
+
+        let avoiding = flood(r, |k| {
+            let targets = self.nodes[&k].edges.keys();
+            targets.filter(|t| **t != d).copied().collect::<Vec<_>>()
+        });
+        !avoiding.contains(&n)
+    }
+
+    pub fn relation(&self, a: &T, b: &T) -> Relation {
+        if self.get(a).is_none() || self.get(b).is_none() {
+            return Relation::Missing;
+        }
+
+        let before = self.descendant_keys(hash(a)).contains(&hash(b));
+        let after = self.descendant_keys(hash(b)).contains(&hash(a));
+        match (before, after) {
+            (true, true) => Relation::Cycle,
+            (true, false) => Relation::Before,
+            (false, true) => Relation::After,
+            (false, false) => Relation::Incomparable,
+        }
+    }
+
+    pub fn roots(&self) -> HashSet<&T> {
+        let in_degrees = self.in_degrees();
+        self.labels(in_degrees.iter().filter(|(_, d)| **d == 0).map(|(k, _)| k))
+    }
+
+    pub fn leaves(&self) -> HashSet<&T> {
+        self.labels(
+            self.nodes
+                .iter()
+                .filter(|(_, n)| n.edges.is_empty())
+                .map(|(k, _)| k),
+        )
+    }
+
+    pub fn reachable_leaves(&self, label: &T) -> Option<HashSet<&T>> {
+        self.get(label)?;
+        let descendants = self.descendant_keys(hash(label));
+        let leaves = descendants
+            .iter()
+            .filter(|k| self.nodes[*k].edges.is_empty());
+        Some(self.labels(leaves))
+    }
+
+    // Edges leaving the subset and edges entering it
+    #[allow(clippy::type_complexity)]
+    pub fn boundary_edges(&self, subset: &HashSet<&T>) -> (Vec<(&T, &T)>, Vec<(&T, &T)>) {
+        let mut outgoing = Vec::new();
+        let mut incoming = Vec::new();
+        for edge in self.edges() {
+            match (subset.contains(edge.from), subset.contains(edge.to)) {
+                (true, false) => outgoing.push((edge.from, edge.to)),
+                (false, true) => incoming.push((edge.from, edge.to)),
+                _ => {}
+            }
+        }
+        (outgoing, incoming)
+    }
+
+    // Descendant count of every node in one reverse topological pass,
+    // None if the graph contains a cycle
+    pub fn all_descendant_counts(&self) -> Option<HashMap<&T, usize>> {
+        let order = self.topological_keys()?;
+        let mut reachable: HashMap<u64, HashSet<u64>> = HashMap::new();
+        for key in order.iter().rev() {
+            let mut descendants = HashSet::new();
+            for target in self.nodes[key].edges.keys() {
+                descendants.insert(*target);
+                descendants.extend(&reachable[target]);
+            }
+            reachable.insert(*key, descendants);
+        }
+
+        let counts = reachable
+            .iter()
+            .map(|(key, descendants)| (&self.nodes[key].label, descendants.len()))
+            .collect();
+        Some(counts)
+    }
+
+    pub fn predecessors(&self, label: &T) -> Option<HashSet<&T>> {
+        self.get(label)?;
+        let key = hash(label);
+        let predecessors = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.edges.contains_key(&key))
+            .map(|(k, _)| k);
+        Some(self.labels(predecessors))
+    }
+
+    // Other nodes sharing at least one direct predecessor
+    pub fn siblings(&self, label: &T) -> Option<HashSet<&T>> {
+        let mut siblings = HashSet::new();
+        for parent in self.predecessors(label)? {
+            siblings.extend(self.connections(parent).unwrap());
+        }
+        siblings.remove(label);
+        Some(siblings)
+    }
+
+    // Fewest nodes whose combined reach covers the graph. For an acyclic graph this is
+    // exactly the roots, including isolated nodes. Each cycle which isn't reachable from
+    // a root adds one of its nodes instead.
+    pub fn minimal_covering_roots(&self) -> Vec<&T> {
+        let mut covered = HashSet::new();
+        let mut chosen = Vec::new();
+        let mut cover = |key: u64, covered: &mut HashSet<u64>| {
+            covered.insert(key);
+            covered.extend(self.descendant_keys(key));
+            chosen.push(&self.nodes[&key].label);
+        };
+
+        for (key, degree) in self.in_degrees() {
+            if degree == 0 {
+                cover(key, &mut covered);
+            }
+        }
+
+        while let Some(mut key) = self.nodes.keys().find(|k| !covered.contains(*k)).copied() {
+            // Move upstream until nothing above is outside this node's reach
+            loop {
+                let mut reach = self.descendant_keys(key);
+                reach.insert(key);
+                match self
+                    .ancestor_keys(key)
+                    .into_iter()
+                    .find(|k| !reach.contains(k))
+                {
+                    Some(ancestor) => key = ancestor,
+                    None => break,
+                }
+            }
+            cover(key, &mut covered);
+        }
+        chosen
+    }
+
+    // Number of distinct nodes reachable from the node, including itself
+    pub fn reachable_size(&self, label: &T) -> Option<usize> {
+        let start = &self.get(label)?.label;
+        Some(self.bfs(start).count())
+    }
+
+    // Each node with how many other nodes would no longer be reachable from the roots
+    // without it, most critical first. Roots are those of the full graph.
+    pub fn critical_nodes(&self) -> Vec<(&T, usize)> {
+        let in_degrees = self.in_degrees();
+        let roots = in_degrees
+            .iter()
+            .filter(|(_, d)| **d == 0)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        let reachable = |skip: Option<u64>| {
+            let mut visited = HashSet::new();
+            let mut stack = roots
+                .iter()
+                .copied()
+                .filter(|k| Some(*k) != skip)
+                .collect::<Vec<_>>();
+            visited.extend(stack.iter().copied());
+            while let Some(key) = stack.pop() {
+                for target in self.nodes[&key].edges.keys() {
+                    if Some(*target) != skip && visited.insert(*target) {
+                        stack.push(*target);
+                    }
+                }
+            }
+            visited
+        };
+
+        let all = reachable(None);
+        let mut critical = self
+            .nodes
+            .iter()
+            .map(|(key, node)| {
+                let kept = reachable(Some(*key)).len() + all.contains(key) as usize;
+                (&node.label, all.len() - kept)
+            })
+            .collect::<Vec<_>>();
+        critical.sort_by_key(|(_, lost)| Reverse(*lost));
+        critical
+    }
+
+    // Descendants of a which aren't descendants of b, empty if a is missing
+    pub fn exclusive_descendants(&self, a: &T, b: &T) -> HashSet<&T> {
+        if self.get(a).is_none() {
+            return HashSet::new();
+        }
+        let excluded = match self.get(b) {
+            Some(_) => self.descendant_keys(hash(b)),
+            None => HashSet::new(),
+        };
+        self.labels(self.descendant_keys(hash(a)).difference(&excluded))
+    }
+
+    // Nodes which are ancestors of every target, empty if there are no targets or any is missing
+    pub fn common_ancestors<'a, I: IntoIterator<Item = &'a T>>(
+        &'a self,
+        targets: I,
+    ) -> HashSet<&'a T> {
+        let predecessors = self.predecessor_keys();
+        let mut common: Option<HashSet<u64>> = None;
+        for target in targets {
+            if self.get(target).is_none() {
+                return HashSet::new();
+            }
+            let ancestors = flood(hash(target), |k| {
+                predecessors[&k].iter().copied().collect::<Vec<_>>()
+            });
+            common = Some(match common {
+                Some(common) => common.intersection(&ancestors).copied().collect(),
+                None => ancestors,
+            });
+        }
+        self.labels(&common.unwrap_or_default())
+    }
+
+    // Descendants still to do, neither counting nor walking past anything already done
+    pub fn descendant_count_excluding(
+        &self,
+        label: &T,
+        already_done: &HashSet<&T>,
+    ) -> Option<usize> {
+        let start = hash(label);
+        self.get(label)?;
+        let done = already_done.iter().map(hash).collect::<HashSet<_>>();
+        let remaining = flood(start, |k| {
+            self.nodes[&k]
+                .edges
+                .keys()
+                .filter(|t| !done.contains(*t))
+                .copied()
+                .collect::<Vec<_>>()
+        });
+        Some(remaining.len() - remaining.contains(&start) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descendants_and_ancestors() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c
+        //      d -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'d', &'c'));
+
+        let descendants = g.descendants(&'a').unwrap();
+        assert_eq!(descendants, [&'b', &'c'].iter().cloned().collect());
+
+        let ancestors = g.ancestors(&'c').unwrap();
+        assert_eq!(ancestors, [&'a', &'b', &'d'].iter().cloned().collect());
+
+        assert!(g.descendants(&'e').is_none());
+    }
+
+    #[test]
+    fn nodes_between() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> d -> f
+        // a -> c -> d
+        //      e -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'e', &'d'));
+        assert!(g.connect(&'d', &'f'));
+
+        let between = g.nodes_between(&'a', &'d');
+        assert_eq!(between, [&'a', &'b', &'c', &'d'].iter().cloned().collect());
+
+        assert!(g.nodes_between(&'a', &'e').is_empty());
+        assert_eq!(g.nodes_between(&'f', &'f').len(), 1);
+    }
+
+    #[test]
+    fn forest_and_tree() {
+        let mut g = Graph::init('a'..='d');
+        assert!(g.is_forest());
+        assert!(!g.is_tree());
+
+        // a -> b
+        // a -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.is_tree());
+
+        // b -> d gives d two parents
+        assert!(g.connect(&'b', &'d'));
+        assert!(!g.is_forest());
+
+        // a <-> b cycle with single parents
+        let mut h = Graph::init('a'..='b');
+        assert!(h.biconnect(&'a', &'b'));
+        assert!(!h.is_forest());
+    }
+
+    #[test]
+    fn highest_common_descendants() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> c -> d -> e
+        // b -> c
+        // b -> d
+        //      f
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        let highest = g.highest_common_descendants(&'a', &'b');
+        assert_eq!(highest, [&'c'].iter().cloned().collect());
+
+        assert!(g.highest_common_descendants(&'a', &'f').is_empty());
+        assert!(g.highest_common_descendants(&'a', &'z').is_empty());
+    }
+
+    #[test]
+    fn isolated_nodes() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b    c    d -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'d', &'d'));
+
+        assert_eq!(g.isolated_nodes(), [&'c'].iter().cloned().collect());
+    }
+
+    #[test]
+    fn component_of() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b <- c    d -> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'c', &'b'));
+        assert!(g.connect(&'d', &'e'));
+
+        let component = g.component_of(&'a').unwrap();
+        assert_eq!(component, [&'a', &'b', &'c'].iter().cloned().collect());
+        assert_eq!(g.component_of(&'e').unwrap().len(), 2);
+        assert!(g.component_of(&'f').is_none());
+    }
+
+    #[test]
+    fn dominates() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> c -> e
+        //      b -> d -> e
+        // a -------> d
+        //                 f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'e'));
+        assert!(g.connect(&'d', &'e'));
+        assert!(g.connect(&'a', &'d'));
+
+        assert!(g.dominates(&'a', &'b', &'c'));
+        assert!(!g.dominates(&'a', &'b', &'e'));
+        assert!(!g.dominates(&'a', &'c', &'e'));
+        assert!(g.dominates(&'a', &'a', &'e'));
+        assert!(g.dominates(&'a', &'e', &'e'));
+        assert!(!g.dominates(&'a', &'b', &'f'));
+        assert!(g.dominates(&'a', &'a', &'a'));
+        assert!(!g.dominates(&'a', &'b', &'a'));
+    }
+
+    #[test]
+    fn relation() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c    d <-> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.biconnect(&'d', &'e'));
+
+        assert_eq!(g.relation(&'a', &'c'), Relation::Before);
+        assert_eq!(g.relation(&'c', &'a'), Relation::After);
+        assert_eq!(g.relation(&'a', &'d'), Relation::Incomparable);
+        assert_eq!(g.relation(&'d', &'e'), Relation::Cycle);
+        assert_eq!(g.relation(&'a', &'f'), Relation::Missing);
+        assert!(g.has_path(&'a', &'c'));
+    }
+
+    #[test]
+    fn roots_and_leaves() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c    d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+
+        assert_eq!(g.roots(), [&'a', &'d'].iter().cloned().collect());
+        assert_eq!(g.leaves(), [&'c', &'d'].iter().cloned().collect());
+    }
+
+    #[test]
+    fn reachable_leaves() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c
+        // a -> d    e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+
+        let leaves = g.reachable_leaves(&'a').unwrap();
+        assert_eq!(leaves, [&'c', &'d'].iter().cloned().collect());
+        assert!(g.reachable_leaves(&'e').unwrap().is_empty());
+        assert!(g.reachable_leaves(&'f').is_none());
+    }
+
+    #[test]
+    fn boundary_edges() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        let subset = [&'b', &'c'].iter().cloned().collect();
+        let (outgoing, incoming) = g.boundary_edges(&subset);
+        assert_eq!(outgoing, vec![(&'c', &'d')]);
+        assert_eq!(incoming, vec![(&'a', &'b')]);
+    }
+
+    #[test]
+    fn all_descendant_counts() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> d -> e
+        // a -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        let counts = g.all_descendant_counts().unwrap();
+        assert_eq!(counts[&'a'], 4);
+        assert_eq!(counts[&'b'], 2);
+        assert_eq!(counts[&'d'], 1);
+        assert_eq!(counts[&'e'], 0);
+    }
+
+    #[test]
+    fn siblings() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b    d -> c
+        // a -> c    d -> e    f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'d', &'c'));
+        assert!(g.connect(&'d', &'e'));
+
+        assert_eq!(
+            g.predecessors(&'c').unwrap(),
+            [&'a', &'d'].iter().cloned().collect()
+        );
+        assert_eq!(
+            g.siblings(&'c').unwrap(),
+            [&'b', &'e'].iter().cloned().collect()
+        );
+        assert!(g.siblings(&'f').unwrap().is_empty());
+        assert!(g.siblings(&'g').is_none());
+    }
+
+    #[test]
+    fn minimal_covering_roots() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b    c    d
+        assert!(g.connect(&'a', &'b'));
+        let mut roots = g.minimal_covering_roots();
+        roots.sort();
+        assert_eq!(roots, vec![&'a', &'c', &'d']);
+
+        // a -> b    c <-> d -> a
+        assert!(g.biconnect(&'c', &'d'));
+        assert!(g.connect(&'d', &'a'));
+        let roots = g.minimal_covering_roots();
+        assert_eq!(roots.len(), 1);
+        assert!(roots[0] == &'c' || roots[0] == &'d');
+    }
+
+    #[test]
+    fn reachable_size() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> d
+        // a -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+
+        assert_eq!(g.reachable_size(&'a'), Some(4));
+        assert_eq!(g.reachable_size(&'d'), Some(1));
+        assert_eq!(g.reachable_size(&'e'), None);
+    }
+
+    #[test]
+    fn critical_nodes() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> c -> d
+        // a -> e -> d
+        //           f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'e'));
+        assert!(g.connect(&'e', &'d'));
+
+        let critical = g.critical_nodes();
+        assert_eq!(critical.len(), 6);
+        assert_eq!(critical[0], (&'a', 4));
+        assert_eq!(critical[1], (&'b', 1));
+        assert!(critical[2..].iter().all(|(_, lost)| *lost == 0));
+    }
+
+    #[test]
+    fn exclusive_descendants() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> d
+        // a -> c -> d -> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        let exclusive = g.exclusive_descendants(&'a', &'b');
+        assert_eq!(exclusive, [&'b', &'c'].iter().cloned().collect());
+        assert!(g.exclusive_descendants(&'b', &'a').is_empty());
+        assert_eq!(g.exclusive_descendants(&'d', &'z').len(), 1);
+        assert!(g.exclusive_descendants(&'z', &'a').is_empty());
+    }
+
+    #[test]
+    fn query_cache() {
+        let mut g = Graph::init('a'..='d').with_query_cache(1);
+
+        // a -> b -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+
+        assert_eq!(g.descendants(&'a').unwrap().len(), 2);
+        assert!(g.has_path(&'a', &'c'));
+        assert!(g.has_path(&'d', &'d'));
+        assert!(!g.has_path(&'c', &'a'));
+        assert!(!g.has_path(&'a', &'e'));
+        assert_eq!(g.descendants(&'a').unwrap().len(), 2);
+
+        assert!(g.connect(&'c', &'d'));
+        assert_eq!(g.descendants(&'a').unwrap().len(), 3);
+        assert!(g.has_path(&'b', &'d'));
+
+        let copy = g.clone();
+        assert!(g.disconnect(&'a', &'b'));
+        assert!(!g.has_path(&'a', &'d'));
+        assert!(copy.has_path(&'a', &'d'));
+    }
+
+    #[test]
+    fn common_ancestors() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> d
+        //      b -> e
+        // c ------> e
+        //           f
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'b', &'e'));
+        assert!(g.connect(&'c', &'e'));
+
+        let common = g.common_ancestors(&['d', 'e']);
+        assert_eq!(common, [&'a', &'b'].iter().cloned().collect());
+        assert_eq!(g.common_ancestors(&['e']).len(), 3);
+        assert!(g.common_ancestors(&['d', 'f']).is_empty());
+        assert!(g.common_ancestors(&['d', 'z']).is_empty());
+        assert!(g.common_ancestors(&[]).is_empty());
+    }
+
+    #[test]
+    fn descendant_count_excluding() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c
+        // a -> d -> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        let none = HashSet::new();
+        assert_eq!(g.descendant_count_excluding(&'a', &none), Some(4));
+
+        let done = [&'b', &'e'].iter().cloned().collect();
+        assert_eq!(g.descendant_count_excluding(&'a', &done), Some(1));
+        assert_eq!(g.descendant_count_excluding(&'z', &done), None);
+
+        assert!(g.connect(&'c', &'a'));
+        assert_eq!(g.descendant_count_excluding(&'a', &none), Some(4));
+    }
+}
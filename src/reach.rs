@@ -0,0 +1,137 @@
+use crate::{graph::*, hash};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// Postorder numbers from one depth first search. The search tree below a node is exactly
+// the numbers from start to post, and anything reachable at all lies between low and post.
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    start: usize,
+    low: usize,
+    post: usize,
+}
+
+// Reachability answered from interval labels, in constant time for most pairs and
+// otherwise by a walk pruned to the nodes whose interval could still contain the target.
+// Borrows the graph so it can't outlive an edit.
+pub struct ReachabilityIndex<'a, T, E = ()> {
+    graph: &'a Graph<T, E>,
+    intervals: HashMap<u64, Interval>,
+}
+
+impl<T, E> Graph<T, E> {
+    // None if the graph contains a cycle
+    pub fn build_reachability_index(&self) -> Option<ReachabilityIndex<'_, T, E>> {
+        let order = self.topological_keys()?;
+        let mut starts = HashMap::new();
+        let mut postorder = Vec::with_capacity(order.len());
+        for root in order {
+            if starts.contains_key(&root) {
+                continue;
+            }
+            starts.insert(root, postorder.len());
+            let mut stack = vec![(root, self.nodes[&root].edges.keys())];
+            while let Some((key, targets)) = stack.last_mut() {
+                match targets.next() {
+                    Some(target) => {
+                        if !starts.contains_key(target) {
+                            starts.insert(*target, postorder.len());
+                            stack.push((*target, self.nodes[target].edges.keys()));
+                        }
+                    }
+                    None => {
+                        postorder.push(*key);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        // Acyclic, so every target is numbered before its source
+        let mut intervals: HashMap<u64, Interval> = HashMap::new();
+        for (post, key) in postorder.iter().enumerate() {
+            let low = self.nodes[key]
+                .edges
+                .keys()
+                .map(|target| intervals[target].low)
+                .fold(starts[key], usize::min);
+            let interval = Interval {
+                start: starts[key],
+                low,
+                post,
+            };
+            intervals.insert(*key, interval);
+        }
+        Some(ReachabilityIndex {
+            graph: self,
+            intervals,
+        })
+    }
+}
+
+impl<'a, T: Hash + Eq, E> ReachabilityIndex<'a, T, E> {
+    // Whether to is reachable from from, a node always reaching itself
+    pub fn reachable(&self, from: &T, to: &T) -> bool {
+        let (a, b) = (hash(from), hash(to));
+        let (outer, inner) = match (self.intervals.get(&a), self.intervals.get(&b)) {
+            (Some(outer), Some(inner)) => (*outer, *inner),
+            _ => return false,
+        };
+        if !Self::may_contain(outer, inner) {
+            return false;
+        }
+
+        let mut visited = HashSet::new();
+        let mut stack = vec![a];
+        while let Some(key) = stack.pop() {
+            let interval = self.intervals[&key];
+            if interval.start <= inner.post && inner.post <= interval.post {
+                return true;
+            }
+            for target in self.graph.nodes[&key].edges.keys() {
+                if Self::may_contain(self.intervals[target], inner) && visited.insert(*target) {
+                    stack.push(*target);
+                }
+            }
+        }
+        false
+    }
+
+    fn may_contain(outer: Interval, inner: Interval) -> bool {
+        outer.low <= inner.low && inner.post <= outer.post
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reachable() {
+        let mut g = Graph::init('a'..='h');
+
+        // a -> b -> c -> d
+        // a -> e -> c
+        //      e -> f
+        // g -> f -> h
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'e'));
+        assert!(g.connect(&'e', &'c'));
+        assert!(g.connect(&'e', &'f'));
+        assert!(g.connect(&'g', &'f'));
+        assert!(g.connect(&'f', &'h'));
+
+        let index = g.build_reachability_index().unwrap();
+        for from in 'a'..='h' {
+            for to in 'a'..='h' {
+                assert_eq!(index.reachable(&from, &to), g.has_path(&from, &to));
+            }
+        }
+        assert!(!index.reachable(&'a', &'z'));
+
+        assert!(g.connect(&'d', &'a'));
+        assert!(g.build_reachability_index().is_none());
+    }
+}
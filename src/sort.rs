@@ -0,0 +1,678 @@
+use crate::{graph::*, hash, Map};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+impl<T, E> Graph<T, E> {
+    pub(crate) fn topological_keys(&self) -> Option<Vec<u64>> {
+        self.kahn(self.in_degrees())
+    }
+
+    // Topological order of only the given keys, ignoring edges leaving them
+    pub(crate) fn topological_keys_within(&self, keys: &HashSet<u64>) -> Option<Vec<u64>> {
+        let mut in_degrees: Map<u64, usize> = keys.iter().map(|k| (*k, 0)).collect();
+        for key in keys {
+            for target in self.nodes[key].edges.keys() {
+                if let Some(degree) = in_degrees.get_mut(target) {
+                    *degree += 1;
+                }
+            }
+        }
+        self.kahn(in_degrees)
+    }
+
+    fn kahn(&self, mut in_degrees: Map<u64, usize>) -> Option<Vec<u64>> {
+        let mut ready = in_degrees
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        let mut order = Vec::with_capacity(in_degrees.len());
+        while let Some(key) = ready.pop() {
+            for target in self.nodes[&key].edges.keys() {
+                if let Some(degree) = in_degrees.get_mut(target) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.push(*target);
+                    }
+                }
+            }
+            order.push(key);
+        }
+
+        if order.len() == in_degrees.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    // Length of the longest chain ending at each node
+    pub(crate) fn depths(&self) -> Option<HashMap<u64, usize>> {
+        let order = self.topological_keys()?;
+        let mut depths: HashMap<u64, usize> = order.iter().map(|k| (*k, 0)).collect();
+        for key in order {
+            let depth = depths[&key];
+            for target in self.nodes[&key].edges.keys() {
+                let target_depth = depths.get_mut(target).unwrap();
+                *target_depth = (*target_depth).max(depth + 1);
+            }
+        }
+        Some(depths)
+    }
+
+    // None if the graph contains a cycle
+    pub fn topological_sort(&self) -> Option<Vec<&T>> {
+        let order = self
+            .topological_keys()?
+            .into_iter()
+            .map(|key| &self.nodes[&key].label)
+            .collect();
+        Some(order)
+    }
+}
+
+impl<T, E> Graph<T, E> {
+    // Nodes on or after a cycle are never yielded
+    pub fn layers_iter(&self) -> LayerIter<'_, T, E> {
+        let in_degrees = self.in_degrees();
+        let ready = in_degrees
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(key, _)| *key)
+            .collect();
+
+        LayerIter {
+            graph: self,
+            in_degrees,
+            ready,
+        }
+    }
+
+    // Every edge's target precedes its source, None if the graph contains a cycle
+    pub fn reverse_topological_sort(&self) -> Option<Vec<&T>> {
+        let mut order = self.topological_sort()?;
+        order.reverse();
+        Some(order)
+    }
+
+    // Edge count of the longest root to leaf path, None if the graph contains a cycle
+    pub fn height(&self) -> Option<usize> {
+        Some(self.depths()?.values().copied().max().unwrap_or(0))
+    }
+
+    // Whether every edge joins a node at an even depth to one at an odd depth or the
+    // other way round, where depth is the longest chain ending at a node. Edges may skip
+    // layers as long as they skip an even number. False if the graph contains a cycle.
+    pub fn is_layered_bipartite(&self) -> bool {
+        let depths = match self.depths() {
+            Some(depths) => depths,
+            None => return false,
+        };
+        self.nodes.iter().all(|(key, node)| {
+            node.edges
+                .keys()
+                .all(|target| depths[key] % 2 != depths[target] % 2)
+        })
+    }
+
+    // Number of nodes at each depth, the longest chain ending at them, so index 0 counts
+    // the roots. None if the graph contains a cycle.
+    pub fn depth_histogram(&self) -> Option<Vec<usize>> {
+        let mut histogram = Vec::new();
+        for depth in self.depths()?.into_values() {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+        }
+        Some(histogram)
+    }
+
+    pub fn topological_layers(&self) -> Vec<Vec<&T>> {
+        self.layers_iter().collect()
+    }
+
+    // Size of the largest topological layer. Each layer is an antichain so this is a lower
+    // bound on the true width (the maximum antichain), not necessarily equal to it.
+    // None if the graph contains a cycle.
+    pub fn width(&self) -> Option<usize> {
+        let (mut seen, mut widest) = (0, 0);
+        for layer in self.layers_iter() {
+            seen += layer.len();
+            widest = widest.max(layer.len());
+        }
+        (seen == self.nodes.len()).then_some(widest)
+    }
+
+    // Largest set of mutually unreachable nodes, the true width. By Dilworth this is the
+    // size of a minimum chain cover, found by maximum matching on the transitive closure.
+    // None if the graph contains a cycle.
+    pub fn maximum_antichain(&self) -> Option<Vec<&T>> {
+        let order = self.topological_keys()?;
+        let index: HashMap<u64, usize> = order.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+        let reach = order
+            .iter()
+            .map(|k| self.descendant_keys(*k).iter().map(|d| index[d]).collect())
+            .collect::<Vec<Vec<usize>>>();
+
+        fn augment(
+            u: usize,
+            reach: &[Vec<usize>],
+            seen: &mut [bool],
+            matched: &mut [Option<usize>],
+        ) -> bool {
+            for &v in &reach[u] {
+                if !seen[v] {
+                    seen[v] = true;
+                    if matched[v].is_none_or(|w| augment(w, reach, seen, matched)) {
+                        matched[v] = Some(u);
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+
+        let n = order.len();
+        let mut match_right = vec![None; n];
+        for u in 0..n {
+            augment(u, &reach, &mut vec![false; n], &mut match_right);
+        }
+        let mut match_left = vec![None; n];
+        for (v, u) in match_right.iter().enumerate() {
+            if let Some(u) = u {
+                match_left[*u] = Some(v);
+            }
+        }
+
+        // Konig: alternating paths from unmatched left nodes give the minimum vertex cover,
+        // whatever it leaves uncovered on both sides is the antichain
+        let mut left = vec![false; n];
+        let mut right = vec![false; n];
+        let mut stack = (0..n)
+            .filter(|u| match_left[*u].is_none())
+            .collect::<Vec<_>>();
+        for u in &stack {
+            left[*u] = true;
+        }
+        while let Some(u) = stack.pop() {
+            for &v in &reach[u] {
+                if Some(v) != match_left[u] && !right[v] {
+                    right[v] = true;
+                    if let Some(w) = match_right[v] {
+                        if !left[w] {
+                            left[w] = true;
+                            stack.push(w);
+                        }
+                    }
+                }
+            }
+        }
+
+        let antichain = (0..n)
+            .filter(|i| left[*i] && !right[*i])
+            .map(|i| &self.nodes[&order[i]].label)
+            .collect();
+        Some(antichain)
+    }
+}
+
+pub struct LayerIter<'a, T, E = ()> {
+    graph: &'a Graph<T, E>,
+    in_degrees: Map<u64, usize>,
+    ready: Vec<u64>,
+}
+
+impl<'a, T, E> Iterator for LayerIter<'a, T, E> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            return None;
+        }
+
+        let mut layer = Vec::with_capacity(self.ready.len());
+        let mut next = Vec::new();
+        for key in self.ready.drain(..) {
+            let node = &self.graph.nodes[&key];
+            for target in node.edges.keys() {
+                let degree = self.in_degrees.get_mut(target).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    next.push(*target);
+                }
+            }
+            layer.push(&node.label);
+        }
+        self.ready = next;
+        Some(layer)
+    }
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    pub fn topological_sort_subset<'a>(&'a self, subset: &HashSet<&'a T>) -> Option<Vec<&'a T>> {
+        let order = self
+            .topological_sort()?
+            .into_iter()
+            .filter(|label| subset.contains(label))
+            .collect();
+        Some(order)
+    }
+
+    pub fn edges_toposorted(&self) -> Option<Vec<(&T, &T)>> {
+        let mut edges = Vec::new();
+        for from in self.topological_sort()? {
+            for to in self.connections(from).unwrap() {
+                edges.push((from, to));
+            }
+        }
+        Some(edges)
+    }
+
+    // Length of the longest chain ending at the node, looking only at its ancestors.
+    // None if the node is missing or a cycle leads into it.
+    pub fn topological_rank(&self, label: &T) -> Option<usize> {
+        let key = hash(label);
+        self.get(label)?;
+        let mut region = self.ancestor_keys(key);
+        region.insert(key);
+
+        let mut depths: HashMap<u64, usize> = HashMap::new();
+        for ancestor in self.topological_keys_within(&region)? {
+            let depth = depths.get(&ancestor).copied().unwrap_or(0);
+            for target in self.nodes[&ancestor].edges.keys() {
+                if region.contains(target) {
+                    let target_depth = depths.entry(*target).or_insert(0);
+                    *target_depth = (*target_depth).max(depth + 1);
+                }
+            }
+        }
+        Some(depths.get(&key).copied().unwrap_or(0))
+    }
+
+    // Whether the graph plus all the proposed edges would have no cycle,
+    // ignoring any proposed edge with a missing end
+    pub fn would_remain_acyclic<'a, I>(&self, edges: I) -> bool
+    where
+        T: 'a,
+        I: IntoIterator<Item = (&'a T, &'a T)>,
+    {
+        let mut extra: HashMap<u64, Vec<u64>> = HashMap::new();
+        let mut in_degrees = self.in_degrees();
+        for (from, to) in edges {
+            let (a, b) = (hash(from), hash(to));
+            if self.nodes.contains_key(&a) && in_degrees.contains_key(&b) {
+                extra.entry(a).or_default().push(b);
+                *in_degrees.get_mut(&b).unwrap() += 1;
+            }
+        }
+
+        let mut ready = in_degrees
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let mut visited = 0;
+        while let Some(key) = ready.pop() {
+            let targets = self.nodes[&key].edges.keys();
+            for target in targets.chain(extra.get(&key).into_iter().flatten()) {
+                let degree = in_degrees.get_mut(target).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(*target);
+                }
+            }
+            visited += 1;
+        }
+        visited == in_degrees.len()
+    }
+
+    // Greedy heuristic, not an optimum: whenever several nodes are ready the next is the
+    // one cheapest to place after the last emitted node. None if the graph contains a cycle.
+    pub fn topological_sort_min_cost<F: Fn(&T, &T) -> i64>(
+        &self,
+        adjacency_cost: F,
+    ) -> Option<Vec<&T>> {
+        let mut in_degrees = self.in_degrees();
+        let mut ready = in_degrees
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        let mut order: Vec<&T> = Vec::with_capacity(in_degrees.len());
+        while !ready.is_empty() {
+            let next = match order.last() {
+                Some(last) => (0..ready.len())
+                    .min_by_key(|i| adjacency_cost(last, &self.nodes[&ready[*i]].label))
+                    .unwrap(),
+                None => 0,
+            };
+            let key = ready.swap_remove(next);
+            for target in self.nodes[&key].edges.keys() {
+                let degree = in_degrees.get_mut(target).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(*target);
+                }
+            }
+            order.push(&self.nodes[&key].label);
+        }
+
+        if order.len() == in_degrees.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    // Whether order holds every node exactly once with each edge's source before its target
+    pub fn is_valid_topological_order(&self, order: &[&T]) -> bool {
+        if order.len() != self.nodes.len() {
+            return false;
+        }
+        let mut positions = HashMap::new();
+        for (i, label) in order.iter().enumerate() {
+            let key = hash(label);
+            if !self.nodes.contains_key(&key) || positions.insert(key, i).is_some() {
+                return false;
+            }
+        }
+        self.nodes.iter().all(|(key, node)| {
+            node.edges
+                .keys()
+                .all(|target| positions[key] < positions[target])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index<T: Eq>(v: &[&T], t: T) -> usize {
+        v.iter().position(|el| el == &&t).unwrap()
+    }
+
+    #[test]
+    fn topological_sort() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c
+        // a -> d -> c
+        //      e -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'c'));
+        assert!(g.connect(&'e', &'c'));
+
+        let order = g.topological_sort().unwrap();
+        assert_eq!(order.len(), 5);
+        for edge in g.edges() {
+            assert!(index(&order, *edge.from) < index(&order, *edge.to));
+        }
+
+        // a -> b -> c -> a
+        assert!(g.connect(&'c', &'a'));
+        assert!(g.topological_sort().is_none());
+    }
+
+    #[test]
+    fn topological_sort_subset() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+
+        let subset = [&'d', &'a', &'c'].iter().cloned().collect();
+        let order = g.topological_sort_subset(&subset).unwrap();
+        assert_eq!(order, vec![&'a', &'c', &'d']);
+    }
+
+    #[test]
+    fn edges_toposorted() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c
+        // a -> d -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'c'));
+
+        let edges = g.edges_toposorted().unwrap();
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges[0].0, &'a');
+        assert_eq!(edges[1].0, &'a');
+        assert_ne!(edges[2].0, &'a');
+    }
+
+    #[test]
+    fn topological_rank() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c
+        // a -------> c
+        //      d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'c'));
+
+        assert_eq!(g.topological_rank(&'a'), Some(0));
+        assert_eq!(g.topological_rank(&'b'), Some(1));
+        assert_eq!(g.topological_rank(&'c'), Some(2));
+        assert_eq!(g.topological_rank(&'d'), Some(0));
+        assert_eq!(g.topological_rank(&'f'), None);
+
+        // c -> e -> d -> e only matters below c
+        assert!(g.connect(&'c', &'e'));
+        assert!(g.connect(&'e', &'d'));
+        assert!(g.connect(&'d', &'e'));
+        assert_eq!(g.topological_rank(&'c'), Some(2));
+        assert_eq!(g.topological_rank(&'e'), None);
+    }
+
+    #[test]
+    fn layers_iter() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> d
+        // c -> d -> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'d'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        let mut layers = g.layers_iter();
+        let mut first = layers.next().unwrap();
+        first.sort();
+        assert_eq!(first, vec![&'a', &'c']);
+        assert_eq!(layers.next(), Some(vec![&'b']));
+        assert_eq!(layers.next(), Some(vec![&'d']));
+        assert_eq!(layers.next(), Some(vec![&'e']));
+        assert_eq!(layers.next(), None);
+    }
+
+    #[test]
+    fn width() {
+        let mut g = Graph::init('a'..='e');
+        assert_eq!(Graph::<char>::new().width(), Some(0));
+
+        // a -> b
+        // a -> c
+        // a -> d -> e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'a', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'e'));
+
+        assert_eq!(g.topological_layers().len(), 3);
+        assert_eq!(g.width(), Some(3));
+
+        assert!(g.connect(&'e', &'a'));
+        assert_eq!(g.width(), None);
+    }
+
+    #[test]
+    fn reverse_topological_sort() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b -> c
+        // a -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+
+        let order = g.reverse_topological_sort().unwrap();
+        for edge in g.edges() {
+            assert!(index(&order, *edge.to) < index(&order, *edge.from));
+        }
+    }
+
+    #[test]
+    fn would_remain_acyclic() {
+        let mut g = Graph::init('a'..='d');
+
+        // a -> b    c -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'c', &'d'));
+
+        assert!(g.would_remain_acyclic(vec![(&'b', &'c')]));
+        assert!(g.would_remain_acyclic(vec![(&'d', &'a')]));
+        assert!(!g.would_remain_acyclic(vec![(&'b', &'c'), (&'d', &'a')]));
+        assert!(!g.would_remain_acyclic(vec![(&'b', &'b')]));
+        assert!(g.would_remain_acyclic(vec![(&'b', &'e')]));
+    }
+
+    #[test]
+    fn height() {
+        let mut g = Graph::init('a'..='d');
+        assert_eq!(Graph::<char>::new().height(), Some(0));
+        assert_eq!(g.height(), Some(0));
+
+        // a -> b -> c
+        // a -> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert_eq!(g.height(), Some(2));
+
+        assert!(g.connect(&'c', &'a'));
+        assert_eq!(g.height(), None);
+    }
+
+    #[test]
+    fn maximum_antichain() {
+        let mut g = Graph::init('a'..='f');
+
+        // a -> b -> c -> d
+        // a -------------> e
+        //           f -> d
+        // Layers are [a, f] [b, e] [c] [d] but {b, e, f} is an antichain
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'e'));
+        assert!(g.connect(&'f', &'d'));
+
+        let antichain = g.maximum_antichain().unwrap();
+        assert_eq!(antichain.len(), 3);
+        for x in &antichain {
+            for y in &antichain {
+                assert!(!g.descendant_keys(hash(*x)).contains(&hash(*y)));
+            }
+        }
+        assert_eq!(g.width(), Some(2));
+    }
+
+    #[test]
+    fn topological_sort_min_cost() {
+        let mut g = Graph::init(vec![0i64, 1, 2, 10, 11, 12]);
+
+        // 0 -> 1 -> 2
+        // 0 -> 11 -> 12
+        //      10 -> 12
+        assert!(g.connect(&0, &1));
+        assert!(g.connect(&1, &2));
+        assert!(g.connect(&0, &11));
+        assert!(g.connect(&11, &12));
+        assert!(g.connect(&10, &12));
+
+        let order = g
+            .topological_sort_min_cost(|last, next| (last - next).abs())
+            .unwrap();
+        assert_eq!(order.len(), 6);
+        for edge in g.edges() {
+            assert!(index(&order, *edge.from) < index(&order, *edge.to));
+        }
+        assert_eq!(index(&order, 1) + 1, index(&order, 2));
+
+        assert!(g.connect(&12, &0));
+        assert!(g.topological_sort_min_cost(|_, _| 0).is_none());
+    }
+
+    #[test]
+    fn is_layered_bipartite() {
+        let mut g = Graph::init('a'..='e');
+
+        // a -> b -> c -> d
+        // a ------------> d
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'c', &'d'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.is_layered_bipartite());
+
+        // a -> e -> c, putting c at depth 2 either way
+        assert!(g.connect(&'a', &'e'));
+        assert!(g.connect(&'e', &'c'));
+        assert!(g.is_layered_bipartite());
+
+        assert!(g.connect(&'a', &'c'));
+        assert!(!g.is_layered_bipartite());
+
+        assert!(g.disconnect(&'a', &'c'));
+        assert!(g.connect(&'d', &'a'));
+        assert!(!g.is_layered_bipartite());
+    }
+
+    #[test]
+    fn depth_histogram() {
+        let mut g = Graph::init('a'..='e');
+        assert_eq!(Graph::<char>::new().depth_histogram(), Some(vec![]));
+
+        // a -> b -> c
+        // a -> d -> c
+        //      e
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+        assert!(g.connect(&'a', &'d'));
+        assert!(g.connect(&'d', &'c'));
+        assert_eq!(g.depth_histogram(), Some(vec![2, 2, 1]));
+
+        assert!(g.connect(&'c', &'a'));
+        assert_eq!(g.depth_histogram(), None);
+    }
+
+    #[test]
+    fn is_valid_topological_order() {
+        let mut g = Graph::init('a'..='c');
+
+        // a -> b -> c
+        assert!(g.connect(&'a', &'b'));
+        assert!(g.connect(&'b', &'c'));
+
+        assert!(g.is_valid_topological_order(&[&'a', &'b', &'c']));
+        assert!(!g.is_valid_topological_order(&[&'a', &'c', &'b']));
+        assert!(!g.is_valid_topological_order(&[&'a', &'b']));
+        assert!(!g.is_valid_topological_order(&[&'a', &'b', &'b']));
+        assert!(!g.is_valid_topological_order(&[&'a', &'b', &'d']));
+
+        let order = g.topological_sort().unwrap();
+        assert!(g.is_valid_topological_order(&order));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{graph::*, hash};
+use std::hash::Hash;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GraphWarning<L> {
+    Isolated(L),
+    MisplacedKey(L), // stored under a key which isn't the hash of its label
+    DanglingEdge { from: L, to: u64 }, // target key with no node
+    OrphanedData { from: L, to: u64 }, // edge data without an edge
+}
+
+impl<T: Hash + Eq, E> Graph<T, E> {
+    pub fn validate(&self) -> Vec<GraphWarning<&T>> {
+        let mut warnings = self
+            .isolated_nodes()
+            .into_iter()
+            .map(GraphWarning::Isolated)
+            .collect::<Vec<_>>();
+
+        for (key, node) in &self.nodes {
+            let from = &node.label;
+            if hash(from) != *key {
+                warnings.push(GraphWarning::MisplacedKey(from));
+            }
+            for to in node.edges.keys() {
+                if !self.nodes.contains_key(to) {
+                    warnings.push(GraphWarning::DanglingEdge { from, to: *to });
+                }
+            }
+            for to in node.data.keys() {
+                if !node.edges.contains_key(to) {
+                    warnings.push(GraphWarning::OrphanedData { from, to: *to });
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let mut g: Graph<char, ()> = Graph::default();
+        g.add('a');
+        g.add('b');
+        g.add('c');
+        assert!(g.connect(&'a', &'b'));
+        assert_eq!(g.validate(), vec![GraphWarning::Isolated(&'c')]);
+
+        let a = g.nodes.get_mut(&hash(&'a')).unwrap();
+        a.edges.insert(hash(&'d'), 1);
+        a.data.insert(hash(&'c'), ());
+
+        let warnings = g.validate();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.contains(&GraphWarning::DanglingEdge {
+            from: &'a',
+            to: hash(&'d')
+        }));
+        assert!(warnings.contains(&GraphWarning::OrphanedData {
+            from: &'a',
+            to: hash(&'c')
+        }));
+    }
+}